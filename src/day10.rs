@@ -1,82 +1,264 @@
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
+use std::str::FromStr;
+use itertools::Itertools;
+
+const REGISTERS: [char; 4] = ['x', 'y', 'z', 'w'];
+
+//letters of the CRT, each glyph is 4 pixels wide and followed by an empty column
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+const FONT: [(char, [&str; GLYPH_HEIGHT]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
 
 #[derive(Debug)]
-enum InstructionType {
-    Noop,
-    Addx(i32),
+struct Opcode {
+    mnemonic: &'static str,
+    //whether the mnemonic is followed by the name of the register it works on, as in addx
+    on_register: bool,
+    nb_operands: usize,
+    cycles: usize,
+    execute: fn(&mut i32, &[i32]),
 }
 
-#[derive(Debug)]
+const INSTRUCTION_SET: [Opcode; 4] = [
+    Opcode { mnemonic: "noop", on_register: false, nb_operands: 0, cycles: 1, execute: |_register, _operands| {} },
+    Opcode { mnemonic: "add", on_register: true, nb_operands: 1, cycles: 2, execute: |register, operands| *register += operands[0] },
+    Opcode { mnemonic: "mul", on_register: true, nb_operands: 1, cycles: 4, execute: |register, operands| *register *= operands[0] },
+    Opcode { mnemonic: "set", on_register: true, nb_operands: 1, cycles: 1, execute: |register, operands| *register = operands[0] },
+];
+
+#[derive(Debug, Clone)]
 struct Instruction {
-    instr_type: InstructionType,
+    opcode: &'static Opcode,
+    register: usize,
+    operands: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Breakpoint {
+    Cycle(usize),
+    //stops when the register takes the value
+    Register(char, i32),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CpuState {
+    Running,
+    Break(Breakpoint),
+    Halted,
+}
+
+#[derive(Debug)]
+struct TraceEntry {
+    cycle: usize,
+    value_x: i32,
+    instruction: String,
+}
+
+struct Cpu {
+    program: Vec<Instruction>,
+    program_counter: usize,
     remaining_cycles: usize,
+    //number of the cycle being executed, starting at 1
+    nb_cycles: usize,
+    registers: [i32; REGISTERS.len()],
+    screen_width: usize,
+    screen_height: usize,
+    screen: Vec<bool>,
+    breakpoints: Vec<Breakpoint>,
+    trace: Option<Vec<TraceEntry>>,
 }
 
-impl Instruction {
-    fn new_noop() -> Self {
-        Instruction {
-            instr_type: InstructionType::Noop,
-            remaining_cycles: 1,
-        }
-    }
+impl FromStr for Instruction {
+    type Err = String;
 
-    fn new_addx(value: i32) -> Self {
-        Instruction {
-            instr_type: InstructionType::Addx(value),
-            remaining_cycles: 2,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = s.split_whitespace();
+        let name = it.next().ok_or("Empty instruction")?;
+        let (opcode, register) = INSTRUCTION_SET.iter()
+            .find_map(|opcode| {
+                let rest = name.strip_prefix(opcode.mnemonic)?;
+                let mut chars = rest.chars();
+                match (opcode.on_register, chars.next(), chars.next()) {
+                    (false, None, None) => Some((opcode, 0)),
+                    (true, Some(c), None) => REGISTERS.iter().position(|&r| r == c).map(|register| (opcode, register)),
+                    _ => None
+                }
+            })
+            .ok_or(format!("Unknown instruction {}", s))?;
+        let operands = it.map(|operand| operand.parse::<i32>().map_err(|e| e.to_string()))
+            .collect::<Result<Vec<i32>,String>>()?;
+        if operands.len() != opcode.nb_operands {
+            return Err(format!("{} expects {} operands", opcode.mnemonic, opcode.nb_operands));
         }
+        Ok(Instruction { opcode, register, operands })
     }
+}
 
-    fn execute_cycle(&mut self) -> (bool,Option<i32>) {
-        self.remaining_cycles -= 1;
-        if self.remaining_cycles == 0 {
-            return match self.instr_type {
-                InstructionType::Noop => (true, None),
-                InstructionType::Addx(v) => (true, Some(v))
-            }
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.opcode.mnemonic)?;
+        if self.opcode.on_register {
+            write!(f, "{}", REGISTERS[self.register])?;
+        }
+        for operand in self.operands.iter() {
+            write!(f, " {}", operand)?;
         }
-        (false,None)
+        Ok(())
     }
-
 }
 
-struct Cpu {
-    nb_cycles: usize,
-    instr_stack : Vec<Instruction>,
-    value_x : i32,
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>5} | X = {:>4} | {}", self.cycle, self.value_x, self.instruction)
+    }
 }
 
 impl Cpu {
-    fn new(instrs: impl Iterator<Item = Instruction>) -> Self {
+    fn new(program: Vec<Instruction>, screen_width: usize, screen_height: usize) -> Self {
+        let remaining_cycles = program.first().map_or(0, |instr| instr.opcode.cycles);
+        let mut registers = [0; REGISTERS.len()];
+        registers[0] = 1;
         Cpu {
+            program,
+            program_counter: 0,
+            remaining_cycles,
             nb_cycles: 1,
-            instr_stack: instrs.collect(),
-            value_x: 1,
+            registers,
+            screen_width,
+            screen_height,
+            screen: vec![false; screen_width * screen_height],
+            breakpoints: Vec::new(),
+            trace: None,
         }
     }
-    fn execute_cycle(&mut self) -> Option<i32> {
+
+    fn value_x(&self) -> i32 {
+        self.registers[0]
+    }
+
+    fn register(&self, name: char) -> Option<i32> {
+        REGISTERS.iter().position(|&r| r == name).map(|i| self.registers[i])
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> Result<(),String> {
+        if let Breakpoint::Register(name, _value) = breakpoint {
+            if !REGISTERS.contains(&name) {
+                return Err(format!("Unknown register {}", name));
+            }
+        }
+        self.breakpoints.push(breakpoint);
+        Ok(())
+    }
+
+    fn enable_trace(&mut self) {
+        self.trace = Some(Vec::new());
+    }
+
+    //executes one cycle, drawing the pixel and finishing the current instruction if it was its last cycle
+    fn step(&mut self) -> CpuState {
+        let Some(instr) = self.program.get(self.program_counter) else {
+            return CpuState::Halted;
+        };
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceEntry { cycle: self.nb_cycles, value_x: self.registers[0], instruction: instr.to_string() });
+        }
+        let screen_px = self.nb_cycles - 1;
+        if screen_px < self.screen.len() {
+            let column = (screen_px % self.screen_width) as i32;
+            self.screen[screen_px] = (column - self.value_x()).abs() <= 1;
+        }
+        let registers_before = self.registers;
+        self.remaining_cycles -= 1;
+        if self.remaining_cycles == 0 {
+            (instr.opcode.execute)(&mut self.registers[instr.register], &instr.operands);
+            self.program_counter += 1;
+            self.remaining_cycles = self.program.get(self.program_counter).map_or(0, |instr| instr.opcode.cycles);
+        }
         self.nb_cycles += 1;
-        if let Some(instr) = self.instr_stack.last_mut() {
-            let (ended,result) = instr.execute_cycle();
-            if ended {
-                self.instr_stack.pop();
-                if let Some(value_to_add) = result {
-                    self.value_x += value_to_add;
-                }
+        //a breakpoint reached by the last instruction is reported before halting
+        let breakpoint = self.breakpoints.iter()
+            .find(|breakpoint| match **breakpoint {
+                Breakpoint::Cycle(cycle) => cycle == self.nb_cycles,
+                Breakpoint::Register(name, value) => REGISTERS.iter()
+                    .position(|&r| r == name)
+                    .is_some_and(|i| registers_before[i] != value && self.registers[i] == value)
+            });
+        match breakpoint {
+            Some(&breakpoint) => CpuState::Break(breakpoint),
+            None if self.program_counter >= self.program.len() => CpuState::Halted,
+            None => CpuState::Running
+        }
+    }
+
+    //runs until a breakpoint is reached or the program ends
+    fn run(&mut self) -> CpuState {
+        loop {
+            match self.step() {
+                CpuState::Running => {}
+                state => return state
             }
-            return None;
         }
-        Some(self.value_x)
     }
 
     fn signal_strength(&self) -> i32 {
-        self.nb_cycles as i32 * self.value_x
+        self.nb_cycles as i32 * self.value_x()
     }
 
-    fn is_sprite_visible(&self) -> bool {
-        let screen_px = (self.nb_cycles % 40) as i32;
-        screen_px >= self.value_x && screen_px <= self.value_x + 2
+    //reads the letters drawn on the screen, the error lists the bitmap of every glyph which is not in the font
+    fn read_screen(&self) -> Result<String,String> {
+        if self.screen_height != GLYPH_HEIGHT {
+            return Err(format!("The screen must be {} pixels high to be read", GLYPH_HEIGHT));
+        }
+        let glyphs = (0..self.screen_width / (GLYPH_WIDTH + 1))
+            .map(|k| (0..GLYPH_HEIGHT)
+                .map(|i| (0..GLYPH_WIDTH)
+                    .map(|j| if self.screen[i * self.screen_width + k * (GLYPH_WIDTH + 1) + j] { '#' } else { '.' })
+                    .collect::<String>())
+                .collect::<Vec<String>>())
+            .collect::<Vec<Vec<String>>>();
+        let mut unknown = Vec::new();
+        let text = glyphs.iter()
+            .enumerate()
+            .map(|(k,glyph)| FONT.iter()
+                .find(|(_letter,bitmap)| bitmap.iter().eq(glyph.iter()))
+                .map_or_else(|| {
+                    unknown.push(format!("Unknown glyph {} :\n{}", k + 1, glyph.join("\n")));
+                    '?'
+                }, |(letter,_bitmap)| *letter))
+            .collect::<String>();
+        if unknown.is_empty() {
+            Ok(text)
+        }
+        else {
+            Err(unknown.join("\n"))
+        }
+    }
+
+    fn screen_string(&self) -> String {
+        self.screen.chunks(self.screen_width)
+            .take(self.screen_height)
+            .map(|row| row.iter().map(|&lit| if lit { '█' } else { ' ' }).collect::<String>())
+            .join("\n")
     }
 }
 
@@ -86,36 +268,112 @@ pub fn day10() {
     file.read_to_string(&mut data)
         .expect("Error while reading file");
 
-    let iter = data.split('\n').
-        map(|line| {
-            let mut it = line.split_whitespace();
-            if it.next().unwrap() == "noop" {
-                Instruction::new_noop()
-            }
-            else {
-                Instruction::new_addx(it.next().unwrap().parse::<i32>().unwrap())
-            }
-        });
-    let mut cpu = Cpu::new(iter.rev());
-    let cycles: Vec<usize> = vec![20,60,100,140,180,220];
+    let program = data.lines()
+        .map(|line| Instruction::from_str(line).unwrap())
+        .collect::<Vec<Instruction>>();
+    let mut cpu = Cpu::new(program.clone(), 40, 6);
+    let signal_cycles = (20..=220).step_by(40);
+    signal_cycles.for_each(|cycle| cpu.add_breakpoint(Breakpoint::Cycle(cycle)).unwrap());
     let mut result1: i32 = 0;
-    let mut result2 = String::from("");
-    match cpu.is_sprite_visible() {
-        true => result2.push('█'),
-        false => result2.push(' ')
-    }
-    while cpu.execute_cycle().is_none() {
-        match cpu.is_sprite_visible() {
-            true => result2.push('█'),
-            false => result2.push(' ')
-        }
-        if cpu.nb_cycles % 40 == 0 {
-            result2.push('\n');
+    while let CpuState::Break(_breakpoint) = cpu.run() {
+        result1 += cpu.signal_strength();
+    }
+    println!("Solution 1 : {:?}", result1);
+    match cpu.read_screen() {
+        Ok(text) => println!("Solution 2 : {:?}", text),
+        Err(e) => println!("Solution 2 : \n{}\n{}", cpu.screen_string(), e)
+    }
+    println!("Final registers : {:?}", REGISTERS.map(|name| (name, cpu.register(name).unwrap())));
+
+    let final_x = cpu.value_x();
+    let mut traced_cpu = Cpu::new(program, 40, 6);
+    traced_cpu.enable_trace();
+    traced_cpu.add_breakpoint(Breakpoint::Register('x', final_x)).unwrap();
+    match traced_cpu.run() {
+        CpuState::Break(_breakpoint) => {
+            println!("X first becomes {} during cycle {}, after :", final_x, traced_cpu.nb_cycles);
+            let trace = traced_cpu.trace.as_ref().unwrap();
+            trace.iter().skip(trace.len().saturating_sub(3)).for_each(|entry| println!("{}", entry));
         }
-        if cycles.contains(&cpu.nb_cycles) {
-            result1 += cpu.signal_strength();
+        _ => println!("X never changes to {}", final_x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_program() -> Vec<Instruction> {
+        "noop\naddx 3\naddx -5".lines().map(|line| Instruction::from_str(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn register_breakpoint() {
+        let mut cpu = Cpu::new(small_program(), 40, 6);
+        cpu.add_breakpoint(Breakpoint::Register('x', 4)).unwrap();
+        assert!(matches!(cpu.run(), CpuState::Break(Breakpoint::Register('x', 4))));
+        assert_eq!(cpu.nb_cycles, 4);
+        assert_eq!(cpu.register('x'), Some(4));
+        assert!(matches!(cpu.run(), CpuState::Halted));
+    }
+
+    #[test]
+    fn breakpoint_on_last_instruction() {
+        let mut cpu = Cpu::new(small_program(), 40, 6);
+        cpu.add_breakpoint(Breakpoint::Register('x', -1)).unwrap();
+        assert!(matches!(cpu.run(), CpuState::Break(Breakpoint::Register('x', -1))));
+        assert_eq!(cpu.nb_cycles, 6);
+        assert!(matches!(cpu.run(), CpuState::Halted));
+    }
+
+    #[test]
+    fn unknown_register_breakpoint() {
+        let mut cpu = Cpu::new(small_program(), 40, 6);
+        assert_eq!(cpu.add_breakpoint(Breakpoint::Register('q', 1)), Err(String::from("Unknown register q")));
+        assert!(matches!(cpu.run(), CpuState::Halted));
+    }
+
+    #[test]
+    fn trace() {
+        let mut cpu = Cpu::new(small_program(), 40, 6);
+        cpu.enable_trace();
+        cpu.run();
+        let trace = cpu.trace.unwrap();
+        assert_eq!(trace.iter().map(|entry| (entry.cycle, entry.value_x)).collect::<Vec<(usize,i32)>>(),
+                   vec![(1,1),(2,1),(3,1),(4,4),(5,4)]);
+        assert_eq!(trace.iter().map(|entry| entry.instruction.as_str()).collect::<Vec<&str>>(),
+                   vec!["noop","addx 3","addx 3","addx -5","addx -5"]);
+        assert_eq!(trace[3].to_string(), "    4 | X =    4 | addx -5");
+    }
+
+    //lights the pixels of the glyphs, one after the other with an empty column between them
+    fn draw(cpu: &mut Cpu, glyphs: &[[&str; GLYPH_HEIGHT]]) {
+        for (k,glyph) in glyphs.iter().enumerate() {
+            for (i,row) in glyph.iter().enumerate() {
+                for (j,c) in row.chars().enumerate() {
+                    cpu.screen[i * cpu.screen_width + k * (GLYPH_WIDTH + 1) + j] = c == '#';
+                }
+            }
         }
     }
-    println!("Solution 1 : {:?}", result1);
-    println!("Solution 2 : \n{}", result2);
-}
\ No newline at end of file
+
+    fn glyph(letter: char) -> [&'static str; GLYPH_HEIGHT] {
+        FONT.iter().find(|(l,_bitmap)| *l == letter).unwrap().1
+    }
+
+    #[test]
+    fn read_word() {
+        let mut cpu = Cpu::new(Vec::new(), 20, 6);
+        draw(&mut cpu, &"HELP".chars().map(glyph).collect::<Vec<[&str; GLYPH_HEIGHT]>>());
+        assert_eq!(cpu.read_screen(), Ok(String::from("HELP")));
+    }
+
+    #[test]
+    fn read_unknown_glyph() {
+        let mut cpu = Cpu::new(Vec::new(), 15, 6);
+        draw(&mut cpu, &[glyph('Z'), ["####", "#..#", "####", "#..#", "####", "#..#"], glyph('A')]);
+        assert_eq!(cpu.read_screen(), Err(String::from("Unknown glyph 2 :\n####\n#..#\n####\n#..#\n####\n#..#")));
+        let cpu = Cpu::new(Vec::new(), 40, 5);
+        assert!(cpu.read_screen().is_err());
+    }
+}
@@ -1,64 +1,242 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
+use itertools::Itertools;
+use num_bigint::BigInt;
 
-#[derive(Debug, Clone, Copy)]
-struct Item {
-    worry_level: u64,
+#[derive(Debug, Clone)]
+enum WorryLevel {
+    Reduced(u64),
+    Exact(BigInt),
 }
 
 #[derive(Debug, Clone)]
-enum OperationType {Plus, Mul}
+struct Item {
+    id: usize,
+    worry_level: WorryLevel,
+    //every (round, monkey) where the item was inspected
+    history: Vec<(usize,MonkeyId)>,
+}
 
+//right side of "new = ...", where old is the current worry level
 #[derive(Debug, Clone)]
-enum Operand {Value(u64), Arg}
+enum Expr {
+    Old,
+    Value(i128),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+}
 
-#[derive(Debug, Clone)]
-struct Operation {
-    op_type: OperationType,
-    operand: Operand,
+#[derive(Debug, Clone, Copy)]
+enum WorryMode {
+    //worry levels are kept modulo the product of the divisibility tests
+    Modular(u64),
+    Exact,
 }
 
 #[derive(Debug, Clone, Copy)]
 struct MonkeyId(usize);
 
+#[derive(Debug, Clone, Copy)]
+pub enum RoundSolver {
+    //plays every round with every item
+    Simulation,
+    //follows each item until its (monkey, worry level) at the start of a round repeats, needs reduced worry levels
+    CycleDetection,
+}
+
 #[derive(Debug, Clone)]
 struct Monkey {
     held_items: Vec<Item>,
-    op_worry: Operation,
+    op_worry: Expr,
     test_div: usize,
     monkey_true: MonkeyId,
     monkey_false: MonkeyId,
     nb_inspections: usize,
-    mod_total: u64,
+}
+
+struct MonkeyGame {
+    monkeys: Vec<Monkey>,
+    new_rules: bool,
+    mode: WorryMode,
+    nb_rounds: usize,
+    //for each round, how many items every monkey inspected
+    inspections_per_round: Vec<Vec<usize>>,
+}
+
+impl Expr {
+    fn eval(&self, old: &BigInt) -> BigInt {
+        match self {
+            Expr::Old => old.clone(),
+            Expr::Value(v) => BigInt::from(*v),
+            Expr::Add(a, b) => a.eval(old) + b.eval(old),
+            Expr::Sub(a, b) => a.eval(old) - b.eval(old),
+            Expr::Mul(a, b) => a.eval(old) * b.eval(old),
+            Expr::Div(a, b) => a.eval(old) / b.eval(old),
+        }
+    }
+
+    //every intermediate result is reduced, which is only correct when is_modular holds
+    fn eval_mod(&self, old: i128, modulus: i128) -> i128 {
+        match self {
+            Expr::Old => old.rem_euclid(modulus),
+            Expr::Value(v) => v.rem_euclid(modulus),
+            Expr::Add(a, b) => (a.eval_mod(old, modulus) + b.eval_mod(old, modulus)).rem_euclid(modulus),
+            Expr::Sub(a, b) => (a.eval_mod(old, modulus) - b.eval_mod(old, modulus)).rem_euclid(modulus),
+            Expr::Mul(a, b) => (a.eval_mod(old, modulus) * b.eval_mod(old, modulus)).rem_euclid(modulus),
+            //a quotient cannot be computed from reduced worry levels, so the modular mode is only chosen for modular expressions
+            Expr::Div(_, _) => unreachable!("Division is not modular, eval_mod is only used when is_modular holds")
+        }
+    }
+
+    //whether the result modulo any number only depends on old modulo this number
+    fn is_modular(&self) -> bool {
+        match self {
+            Expr::Old | Expr::Value(_) => true,
+            Expr::Add(a, b) | Expr::Sub(a, b) | Expr::Mul(a, b) => a.is_modular() && b.is_modular(),
+            Expr::Div(_, _) => false,
+        }
+    }
+}
+
+//recursive descent over the tokens, with the usual precedence of * and / over + and -
+struct ExprParser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn new(s: &'a str) -> Self {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (i, c) in s.char_indices() {
+            if c.is_ascii_alphanumeric() {
+                start.get_or_insert(i);
+                continue;
+            }
+            if let Some(st) = start.take() {
+                tokens.push(&s[st..i]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&s[i..i + c.len_utf8()]);
+            }
+        }
+        if let Some(st) = start {
+            tokens.push(&s[st..]);
+        }
+        ExprParser { tokens, position: 0 }
+    }
+
+    fn next_token(&mut self) -> Option<&'a str> {
+        let token = self.tokens.get(self.position).copied();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr,String> {
+        let mut expr = self.parse_term()?;
+        while let Some(op) = self.peek().filter(|&t| t == "+" || t == "-") {
+            self.position += 1;
+            let rhs = self.parse_term()?;
+            expr = match op {
+                "+" => Expr::Add(Box::new(expr), Box::new(rhs)),
+                _ => Expr::Sub(Box::new(expr), Box::new(rhs)),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_term(&mut self) -> Result<Expr,String> {
+        let mut expr = self.parse_factor()?;
+        while let Some(op) = self.peek().filter(|&t| t == "*" || t == "/") {
+            self.position += 1;
+            let rhs = self.parse_factor()?;
+            expr = match op {
+                "*" => Expr::Mul(Box::new(expr), Box::new(rhs)),
+                _ => Expr::Div(Box::new(expr), Box::new(rhs)),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_factor(&mut self) -> Result<Expr,String> {
+        match self.next_token() {
+            Some("old") => Ok(Expr::Old),
+            Some("(") => {
+                let expr = self.parse_expr()?;
+                match self.next_token() {
+                    Some(")") => Ok(expr),
+                    _ => Err(String::from("Missing closing parenthesis"))
+                }
+            }
+            Some(token) => token.parse::<i128>()
+                .map(Expr::Value)
+                .map_err(|_| format!("Unexpected token {}", token)),
+            None => Err(String::from("Unexpected end of operation"))
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = ExprParser::new(s);
+        let expr = parser.parse_expr()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected token {}", token))
+        }
+    }
+}
+
+impl FromStr for RoundSolver {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "simulation" => Ok(RoundSolver::Simulation),
+            "cycles" => Ok(RoundSolver::CycleDetection),
+            other => Err(format!("Unknown solver {}, expected simulation or cycles", other))
+        }
+    }
 }
 
 impl Monkey {
-    fn play_turn(&mut self, new_rules: bool) -> Vec<(Item,MonkeyId)> {
-        let thrown_items: Vec<(Item, MonkeyId)> = self.held_items.iter()
-            .map(|i| {
-                let new_item = self.apply_op(i, new_rules);
-                let monkey_id = if new_item.worry_level % self.test_div as u64 == 0 {
+    fn play_turn(&mut self, new_rules: bool, mode: WorryMode, round: usize, id: MonkeyId) -> Vec<(Item,MonkeyId)> {
+        let thrown_items: Vec<(Item, MonkeyId)> = std::mem::take(&mut self.held_items).into_iter()
+            .map(|mut item| {
+                item.worry_level = self.apply_op(&item.worry_level, new_rules, mode);
+                item.history.push((round, id));
+                let divisible = match &item.worry_level {
+                    WorryLevel::Reduced(w) => w.is_multiple_of(self.test_div as u64),
+                    WorryLevel::Exact(w) => w % self.test_div == BigInt::from(0),
+                };
+                let monkey_id = if divisible {
                     self.monkey_true
                 } else {
                     self.monkey_false
                 };
-                (new_item,monkey_id)
+                (item,monkey_id)
             }).collect();
-        self.nb_inspections += self.held_items.len();
-        self.held_items = Vec::new();
+        self.nb_inspections += thrown_items.len();
         thrown_items
     }
 
-    fn apply_op(&self, item: &Item, new_rules: bool) -> Item {
-        let operand = match self.op_worry.operand {
-            Operand::Value(v) => v,
-            Operand::Arg => item.worry_level
-        };
+    fn apply_op(&self, worry_level: &WorryLevel, new_rules: bool, mode: WorryMode) -> WorryLevel {
         let divider = if new_rules {1} else {3};
-        match self.op_worry.op_type {
-            OperationType::Plus => Item { worry_level: ((item.worry_level + operand)/divider) % self.mod_total },
-            OperationType::Mul  => Item { worry_level: ((item.worry_level * operand)/divider) % self.mod_total }
+        match (worry_level, mode) {
+            (WorryLevel::Reduced(w), WorryMode::Modular(mod_total)) =>
+                WorryLevel::Reduced(self.op_worry.eval_mod(*w as i128, mod_total as i128) as u64),
+            (WorryLevel::Exact(w), WorryMode::Exact) => WorryLevel::Exact(self.op_worry.eval(w) / divider),
+            _ => unreachable!("Worry levels are converted to the mode when the game starts")
         }
     }
 }
@@ -74,23 +252,11 @@ impl FromStr for Monkey {
         let held_items: Vec<Item> = items.split(": ")
             .nth(1).unwrap()
             .split(", ")
-            .map(|nb| Item { worry_level: nb.parse::<u64>().unwrap() })
+            .map(|nb| Item { id: 0, worry_level: WorryLevel::Exact(nb.parse::<BigInt>().unwrap()), history: Vec::new() })
             .collect();
         //operation
         let op = lines.next().unwrap();
-        let mut ops = op.split("= old ")
-            .nth(1).unwrap()
-            .split_whitespace();
-        let op_type = match ops.next().unwrap() {
-            "+" => OperationType::Plus,
-            "*" => OperationType::Mul,
-            _ => panic!()
-        };
-        let operand = match ops.next().unwrap() {
-            "old" => Operand::Arg,
-            s => Operand::Value(s.parse::<u64>().unwrap())
-        };
-        let op_worry = Operation { op_type, operand };
+        let op_worry = Expr::from_str(op.split("= ").nth(1).ok_or("Missing operation")?)?;
         //div test and who to throw to
         let mut iter_last_nums = lines.map(|s|
             s.split_whitespace()
@@ -106,52 +272,377 @@ impl FromStr for Monkey {
             monkey_true,
             monkey_false,
             nb_inspections: 0,
-            mod_total: 0
         })
     }
 }
 
-fn play_rounds(monkeys: &mut[Monkey], nb_rounds: usize, new_rules: bool) -> usize {
-    let nb_monkeys = monkeys.len();
-    for _ in 0..nb_rounds {
-        for index in 0..nb_monkeys {
-            let monkey = &mut monkeys[index];
-            let thrown_items = monkey.play_turn(new_rules);
-            for (item, monkey_id) in thrown_items.iter() {
-                monkeys[monkey_id.0].held_items.push(*item);
+//keeping worry levels modulo the product of the tests only works without relief and with modular operations
+fn worry_mode(monkeys: &[Monkey], new_rules: bool) -> WorryMode {
+    if new_rules && monkeys.iter().all(|m| m.op_worry.is_modular()) {
+        let mod_total: usize = monkeys.iter()
+            .map(|m| m.test_div)
+            .product();
+        WorryMode::Modular(mod_total as u64)
+    }
+    else {
+        WorryMode::Exact
+    }
+}
+
+impl MonkeyGame {
+    //exact keeps the true worry levels as big integers, even when they could be reduced
+    fn new(mut monkeys: Vec<Monkey>, new_rules: bool, exact: bool) -> Self {
+        let mode = if exact { WorryMode::Exact } else { worry_mode(&monkeys, new_rules) };
+        let mut id = 0;
+        for monkey in monkeys.iter_mut() {
+            for item in monkey.held_items.iter_mut() {
+                item.id = id;
+                id += 1;
+                if let (WorryLevel::Exact(w), WorryMode::Modular(mod_total)) = (&item.worry_level, mode) {
+                    let reduced = w % mod_total;
+                    item.worry_level = WorryLevel::Reduced(u64::try_from(reduced).unwrap());
+                }
+            }
+        }
+        MonkeyGame { monkeys, new_rules, mode, nb_rounds: 0, inspections_per_round: Vec::new() }
+    }
+
+    fn play_round(&mut self) {
+        let inspections_before = self.monkeys.iter().map(|m| m.nb_inspections).collect::<Vec<usize>>();
+        for index in 0..self.monkeys.len() {
+            let monkey = &mut self.monkeys[index];
+            let thrown_items = monkey.play_turn(self.new_rules, self.mode, self.nb_rounds, MonkeyId(index));
+            for (item, monkey_id) in thrown_items.into_iter() {
+                self.monkeys[monkey_id.0].held_items.push(item);
+            }
+        }
+        self.inspections_per_round.push(self.monkeys.iter()
+            .zip(inspections_before)
+            .map(|(m, before)| m.nb_inspections - before)
+            .collect());
+        self.nb_rounds += 1;
+    }
+
+    fn play_rounds(&mut self, nb_rounds: usize) {
+        for _ in 0..nb_rounds {
+            self.play_round();
+        }
+    }
+
+    //plays one round for a single item held by the monkey, adds the inspections and returns who holds it at the end
+    fn route_round(&self, mut monkey: usize, mut worry_level: u64, mod_total: u64, counts: &mut [u64]) -> (usize,u64) {
+        loop {
+            let m = &self.monkeys[monkey];
+            counts[monkey] += 1;
+            worry_level = m.op_worry.eval_mod(worry_level as i128, mod_total as i128) as u64;
+            let next = if worry_level.is_multiple_of(m.test_div as u64) { m.monkey_true.0 } else { m.monkey_false.0 };
+            //monkeys play in order, so an item thrown to a later monkey is inspected again in the same round
+            if next <= monkey {
+                return (next, worry_level);
+            }
+            monkey = next;
+        }
+    }
+
+    //number of inspections of every monkey over the next rounds, in a time which does not depend on the number of rounds
+    fn inspections_by_cycles(&self, nb_rounds: u64) -> Result<Vec<u64>,String> {
+        let WorryMode::Modular(mod_total) = self.mode else {
+            return Err(String::from("Cycle detection needs worry levels reduced modulo the tests"));
+        };
+        let nb_monkeys = self.monkeys.len();
+        let mut total = self.monkeys.iter().map(|m| m.nb_inspections as u64).collect::<Vec<u64>>();
+        for (monkey, m) in self.monkeys.iter().enumerate() {
+            for item in m.held_items.iter() {
+                let WorryLevel::Reduced(worry_level) = item.worry_level else { unreachable!() };
+                let mut seen: HashMap<(usize,u64),usize> = HashMap::new();
+                //inspections done by every monkey in the first k rounds
+                let mut cumulated = vec![vec![0; nb_monkeys]];
+                let mut state = (monkey, worry_level);
+                let mut round = 0;
+                let cycle_start = loop {
+                    if round as u64 == nb_rounds {
+                        break None;
+                    }
+                    if let Some(&start) = seen.get(&state) {
+                        break Some(start);
+                    }
+                    seen.insert(state, round);
+                    let mut counts = cumulated[round].clone();
+                    state = self.route_round(state.0, state.1, mod_total, &mut counts);
+                    cumulated.push(counts);
+                    round += 1;
+                };
+                let item_counts = match cycle_start {
+                    None => cumulated[round].clone(),
+                    Some(start) => {
+                        let period = (round - start) as u64;
+                        let nb_cycles = (nb_rounds - start as u64) / period;
+                        let remainder = ((nb_rounds - start as u64) % period) as usize;
+                        (0..nb_monkeys)
+                            .map(|i| cumulated[start + remainder][i]
+                                + nb_cycles * (cumulated[round][i] - cumulated[start][i]))
+                            .collect()
+                    }
+                };
+                total.iter_mut().zip(item_counts).for_each(|(t, c)| *t += c);
+            }
+        }
+        Ok(total)
+    }
+
+    //monkey business after the next rounds, the cycle detection does not change the game
+    fn monkey_business_after(&mut self, nb_rounds: u64, solver: RoundSolver) -> Result<u128,String> {
+        let inspections = match solver {
+            RoundSolver::Simulation => {
+                self.play_rounds(nb_rounds as usize);
+                self.monkeys.iter().map(|m| m.nb_inspections as u64).collect()
             }
+            RoundSolver::CycleDetection => self.inspections_by_cycles(nb_rounds)?
+        };
+        Ok(inspections.into_iter().sorted_unstable().rev().take(2).map(|n| n as u128).product())
+    }
+
+    fn monkey_business(&self) -> usize {
+        self.monkeys.iter()
+            .map(|m| m.nb_inspections)
+            .sorted_unstable()
+            .rev()
+            .take(2)
+            .product()
+    }
+
+    #[cfg(test)]
+    fn items(&self) -> Vec<&Item> {
+        self.monkeys.iter()
+            .flat_map(|m| m.held_items.iter())
+            .sorted_by_key(|item| item.id)
+            .collect()
+    }
+
+    //monkeys which inspected the item during each round, in order
+    #[cfg(test)]
+    fn throw_history(&self, item: &Item) -> Vec<Vec<usize>> {
+        let mut history = vec![Vec::new(); self.nb_rounds];
+        for (round, monkey_id) in item.history.iter() {
+            history[*round].push(monkey_id.0);
         }
+        history
+    }
+
+    //one line per round with the number of inspections of every monkey
+    fn inspection_table(&self) -> String {
+        let header = std::iter::once(String::from("round"))
+            .chain((0..self.monkeys.len()).map(|i| format!("monkey {}", i)))
+            .join(";");
+        std::iter::once(header)
+            .chain(self.inspections_per_round.iter()
+                .enumerate()
+                .map(|(round, counts)| std::iter::once(round + 1).chain(counts.iter().copied()).join(";")))
+            .join("\n")
     }
-    let mut nb_inspections = monkeys.iter().map(|m| m.nb_inspections).collect::<Vec<usize>>();
-    nb_inspections.sort_unstable();
-    nb_inspections[nb_monkeys-1] * nb_inspections[nb_monkeys-2]
 }
 
-pub fn day11() {
+pub fn day11(solver: RoundSolver) {
     let mut file = File::open("./inputs/input_day11.txt").expect("File not found");
     let mut data = String::new();
     file.read_to_string(&mut data)
         .expect("Error while reading file");
 
-    let mut monkeys: Vec<Monkey> = data.split("\n\n")
+    let monkeys: Vec<Monkey> = data.split("\n\n")
         .map(|s| Monkey::from_str(s).unwrap())
         .collect();
 
-    let mod_total: usize = monkeys.iter()
-        .map(|m| m.test_div)
-        .product();
+    let nb_rounds_b = 20;
+    let nb_rounds_n = 10000;
 
-    monkeys.iter_mut()
-        .for_each(|m| m.mod_total = mod_total as u64);
+    let mut game = MonkeyGame::new(monkeys.clone(), false, true);
+    game.play_rounds(nb_rounds_b);
+    let mut game_new_rules = MonkeyGame::new(monkeys, true, false);
 
-    let mut monkeys_new_rules = monkeys.clone();
+    println!("Solution 1 : {}",game.monkey_business());
+    match game_new_rules.monkey_business_after(nb_rounds_n, solver) {
+        Ok(monkey_business) => println!("Solution 2 : {}",monkey_business),
+        Err(e) => println!("Solution 2 : {}",e)
+    }
+    //the cycle detection leaves the game as it is, so the rounds can be counted from the start again
+    if let RoundSolver::CycleDetection = solver {
+        match game_new_rules.monkey_business_after(1_000_000_000_000, solver) {
+            Ok(monkey_business) => println!("Monkey business after 10^12 rounds : {}",monkey_business),
+            Err(e) => println!("Monkey business after 10^12 rounds : {}",e)
+        }
+    }
 
-    let nb_rounds_b = 20;
-    let nb_rounds_n = 10000;
+    println!("{}", game.inspection_table());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1";
+
+    fn example_monkeys() -> Vec<Monkey> {
+        EXAMPLE.split("\n\n").map(|s| Monkey::from_str(s).unwrap()).collect()
+    }
 
-    let monkey_business = play_rounds(monkeys.as_mut_slice(),nb_rounds_b,false);
-    let monkey_business_new = play_rounds(monkeys_new_rules.as_mut_slice(),nb_rounds_n,true);
+    fn eval(expr: &str, old: i64) -> BigInt {
+        Expr::from_str(expr).unwrap().eval(&BigInt::from(old))
+    }
+
+    #[test]
+    fn parse_precedence_and_parentheses() {
+        assert_eq!(eval("old + 2 * 3", 1), BigInt::from(7));
+        assert_eq!(eval("(old + 2) * 3", 1), BigInt::from(9));
+        assert_eq!(eval("2 * (3 + old) * old", 2), BigInt::from(20));
+        assert_eq!(eval("old - 2 - 3", 10), BigInt::from(5));
+        assert_eq!(eval("old / 2 / 3", 36), BigInt::from(6));
+        assert_eq!(eval("old * old - old", 5), BigInt::from(20));
+        assert_eq!(eval("((old))", 4), BigInt::from(4));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Expr::from_str("old +").err(), Some(String::from("Unexpected end of operation")));
+        assert_eq!(Expr::from_str("(old + 1").err(), Some(String::from("Missing closing parenthesis")));
+        assert_eq!(Expr::from_str("old ? 2").err(), Some(String::from("Unexpected token ?")));
+        assert_eq!(Expr::from_str("old 2").err(), Some(String::from("Unexpected token 2")));
+        assert_eq!(Expr::from_str("new * 2").err(), Some(String::from("Unexpected token new")));
+    }
 
-    println!("Solution 1 : {}",monkey_business);
-    println!("Solution 2 : {}",monkey_business_new);
-}
\ No newline at end of file
+    #[test]
+    fn modular_evaluation() {
+        for (expr,old,modulus) in [("old * old - 7", 5, 13), ("old - 100", 3, 7), ("(old + 4) * old * 3", 11, 17)] {
+            let parsed = Expr::from_str(expr).unwrap();
+            assert!(parsed.is_modular());
+            let expected = (eval(expr, old) % modulus + modulus) % modulus;
+            assert_eq!(BigInt::from(parsed.eval_mod(old as i128, modulus as i128)), expected, "{}", expr);
+        }
+        assert!(!Expr::from_str("old / 2").unwrap().is_modular());
+        assert!(!Expr::from_str("(old + 1) / 3 * 2").unwrap().is_modular());
+    }
+
+    #[test]
+    fn worry_modes() {
+        let monkeys = example_monkeys();
+        assert!(matches!(worry_mode(&monkeys, true), WorryMode::Modular(96577)));
+        assert!(matches!(worry_mode(&monkeys, false), WorryMode::Exact));
+        let game = MonkeyGame::new(monkeys.clone(), true, false);
+        assert!(game.items().iter().all(|item| matches!(item.worry_level, WorryLevel::Reduced(_))));
+        let game = MonkeyGame::new(monkeys, true, true);
+        assert!(matches!(game.mode, WorryMode::Exact));
+    }
+
+    #[test]
+    fn division_forces_exact_mode() {
+        let mut monkeys = example_monkeys();
+        monkeys[3].op_worry = Expr::from_str("old / 2 + 3").unwrap();
+        assert!(matches!(worry_mode(&monkeys, true), WorryMode::Exact));
+        let mut game = MonkeyGame::new(monkeys, true, false);
+        assert!(matches!(game.mode, WorryMode::Exact));
+        assert!(game.inspections_by_cycles(20).is_err());
+        //the item of monkey 3 is 74, which becomes 74 / 2 + 3 = 40 and is thrown to monkey 1, who has already played
+        game.play_round();
+        assert!(game.items().iter().all(|item| matches!(item.worry_level, WorryLevel::Exact(_))));
+        let item = game.monkeys[1].held_items.iter().find(|item| item.id == 9).unwrap();
+        assert!(matches!(&item.worry_level, WorryLevel::Exact(w) if *w == BigInt::from(40)));
+    }
+
+    #[test]
+    fn throw_history() {
+        let mut game = MonkeyGame::new(example_monkeys(), false, true);
+        game.play_rounds(2);
+        let items = game.items();
+        //74 goes from monkey 3 to monkey 1, then to monkey 0 which has already played the second round
+        assert_eq!(game.throw_history(items[9]), vec![vec![3], vec![1]]);
+        assert!(matches!(&items[9].worry_level, WorryLevel::Exact(w) if *w == BigInt::from(10)));
+        assert_eq!(game.throw_history(items[0]), vec![vec![0, 3], vec![1, 2, 3]]);
+        assert!(matches!(&items[0].worry_level, WorryLevel::Exact(w) if *w == BigInt::from(362)));
+    }
+
+    #[test]
+    fn inspection_table() {
+        let mut game = MonkeyGame::new(example_monkeys(), true, false);
+        game.play_rounds(20);
+        let table = game.inspection_table();
+        let mut lines = table.lines();
+        assert_eq!(lines.next(), Some("round;monkey 0;monkey 1;monkey 2;monkey 3"));
+        assert_eq!(lines.next(), Some("1;2;4;3;6"));
+        assert_eq!(lines.count(), 19);
+        let totals = (0..4)
+            .map(|i| game.inspections_per_round.iter().map(|counts| counts[i]).sum::<usize>())
+            .collect::<Vec<usize>>();
+        assert_eq!(totals, vec![99, 97, 8, 103]);
+    }
+
+    #[test]
+    fn cycles_match_simulation() {
+        for nb_rounds in [1, 20, 1000, 10000] {
+            let mut simulated = MonkeyGame::new(example_monkeys(), true, false);
+            let game = MonkeyGame::new(example_monkeys(), true, false);
+            let by_cycles = game.inspections_by_cycles(nb_rounds).unwrap();
+            simulated.play_rounds(nb_rounds as usize);
+            let expected = simulated.monkeys.iter().map(|m| m.nb_inspections as u64).collect::<Vec<u64>>();
+            assert_eq!(by_cycles, expected, "{} rounds", nb_rounds);
+        }
+    }
+
+    #[test]
+    fn cycles_resume_after_simulated_rounds() {
+        let mut simulated = MonkeyGame::new(example_monkeys(), true, false);
+        let mut game = MonkeyGame::new(example_monkeys(), true, false);
+        simulated.play_rounds(7);
+        game.play_rounds(7);
+        for nb_rounds in [1, 20, 993] {
+            let mut resumed = MonkeyGame::new(example_monkeys(), true, false);
+            resumed.play_rounds(7 + nb_rounds);
+            let expected = resumed.monkeys.iter().map(|m| m.nb_inspections as u64).collect::<Vec<u64>>();
+            assert_eq!(game.inspections_by_cycles(nb_rounds as u64), Ok(expected), "{} rounds", nb_rounds);
+        }
+        assert_eq!(game.monkey_business_after(993, RoundSolver::CycleDetection),
+                   simulated.monkey_business_after(993, RoundSolver::Simulation));
+    }
+
+    #[test]
+    fn cycles_need_reduced_worry_levels() {
+        let game = MonkeyGame::new(example_monkeys(), true, true);
+        assert!(game.inspections_by_cycles(20).is_err());
+        assert!(RoundSolver::from_str("cycles").is_ok_and(|solver| matches!(solver, RoundSolver::CycleDetection)));
+        assert!(RoundSolver::from_str("fast").is_err());
+    }
+
+    #[test]
+    fn solve_example() {
+        let mut game = MonkeyGame::new(example_monkeys(), false, true);
+        game.play_rounds(20);
+        assert_eq!(game.monkey_business(), 10605);
+        let mut game = MonkeyGame::new(example_monkeys(), true, false);
+        assert_eq!(game.monkey_business_after(10000, RoundSolver::CycleDetection), Ok(2713310158));
+        assert_eq!(game.monkey_business_after(10000, RoundSolver::Simulation), Ok(2713310158));
+    }
+}
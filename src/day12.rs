@@ -1,26 +1,111 @@
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::Read;
-use ndarray::{Array2, Axis, Dimension};
-use pathfinding::prelude::dijkstra;
+use std::str::FromStr;
+use ndarray::{Array2, Dimension};
 
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 struct Pos(i32,i32);
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+const STRAIGHT_DIRECTIONS: [Direction; 4] = [Direction::Down, Direction::Up, Direction::Right, Direction::Left];
+const DIAGONAL_DIRECTIONS: [Direction; 4] = [Direction::UpLeft, Direction::UpRight, Direction::DownLeft, Direction::DownRight];
+
+#[derive(Clone, Copy, Debug)]
+struct ClimbingRules {
+    max_ascent: u32,
+    //None when any step down is allowed
+    max_descent: Option<u32>,
+    diagonal_moves: bool,
+}
+
+impl Default for ClimbingRules {
+    fn default() -> Self {
+        ClimbingRules { max_ascent: 1, max_descent: None, diagonal_moves: false }
+    }
+}
+
+struct Heightmap {
+    elevations: Array2<u32>,
+    start: Pos,
+    end: Pos,
+}
+
+impl Direction {
+    fn displacement(&self) -> (i32,i32) {
+        match self {
+            Direction::Up => (-1,0),
+            Direction::Down => (1,0),
+            Direction::Left => (0,-1),
+            Direction::Right => (0,1),
+            Direction::UpLeft => (-1,-1),
+            Direction::UpRight => (-1,1),
+            Direction::DownLeft => (1,-1),
+            Direction::DownRight => (1,1),
+        }
+    }
+
+    fn arrow(&self) -> char {
+        match self {
+            Direction::Up => '^',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+            Direction::Right => '>',
+            Direction::UpLeft => '↖',
+            Direction::UpRight => '↗',
+            Direction::DownLeft => '↙',
+            Direction::DownRight => '↘',
+        }
+    }
+}
+
+impl ClimbingRules {
+    fn can_move(&self, from_elevation: u32, to_elevation: u32) -> bool {
+        to_elevation <= from_elevation + self.max_ascent &&
+            self.max_descent.is_none_or(|max_descent| from_elevation <= to_elevation + max_descent)
+    }
+
+    fn directions(&self) -> Vec<Direction> {
+        if self.diagonal_moves {
+            [STRAIGHT_DIRECTIONS, DIAGONAL_DIRECTIONS].concat()
+        }
+        else {
+            STRAIGHT_DIRECTIONS.to_vec()
+        }
+    }
+}
+
 impl Pos {
-    fn neighbors(&self, grid: &Array2<char>) -> Vec<(Pos, usize)> {
+    fn neighbors(&self, elevations: &Array2<u32>, rules: &ClimbingRules) -> Vec<(Pos,Direction)> {
         let &Pos(x,y) = self;
-        let (height,width) = grid.raw_dim().into_pattern();
-        let own_elevation = convert_to_elevation(grid[[x as usize,y as usize]]);
-        vec![(x+1,y),(x-1,y),(x,y+1),(x,y-1)]
+        let (height,width) = elevations.raw_dim().into_pattern();
+        rules.directions()
             .into_iter()
-            .filter(|(u,v)| *u >= 0 && *v >= 0 && *u < height as i32 && *v < width as i32 &&
-                convert_to_elevation(grid[[*u as usize,*v as usize]]) <= own_elevation + 1)
-            .map(|(u,v)| (Pos(u,v),1))
+            .map(|direction| {
+                let (dx,dy) = direction.displacement();
+                (x + dx,y + dy,direction)
+            })
+            .filter(|(u,v,_direction)| *u >= 0 && *v >= 0 && *u < height as i32 && *v < width as i32)
+            .map(|(u,v,direction)| (Pos(u,v),direction))
             .collect()
+    }
 
-
+    fn index(&self) -> [usize; 2] {
+        [self.0 as usize, self.1 as usize]
     }
 }
+
 fn convert_to_elevation(c : char) -> u32 {
     match c {
         'S' => 0,
@@ -28,39 +113,210 @@ fn convert_to_elevation(c : char) -> u32 {
         c => c as u32 - 'a' as u32
     }
 }
+
+impl Heightmap {
+    //number of steps from every cell to the end, found with a single breadth-first search going backwards from the end
+    fn distances_to_end(&self, rules: &ClimbingRules) -> Array2<Option<usize>> {
+        let mut distances: Array2<Option<usize>> = Array2::from_elem(self.elevations.raw_dim(), None);
+        distances[self.end.index()] = Some(0);
+        let mut to_visit = VecDeque::from([self.end]);
+        while let Some(pos) = to_visit.pop_front() {
+            let distance = distances[pos.index()].unwrap();
+            let elevation = self.elevations[pos.index()];
+            for (previous,_direction) in pos.neighbors(&self.elevations, rules) {
+                if distances[previous.index()].is_none() && rules.can_move(self.elevations[previous.index()], elevation) {
+                    distances[previous.index()] = Some(distance + 1);
+                    to_visit.push_back(previous);
+                }
+            }
+        }
+        distances
+    }
+
+    //a shortest route from the cell to the end, with the direction taken from every cell of the route
+    fn route(&self, from: Pos, rules: &ClimbingRules, distances: &Array2<Option<usize>>) -> Option<Vec<(Pos,Direction)>> {
+        let mut distance = distances[from.index()]?;
+        let mut pos = from;
+        let mut route = Vec::with_capacity(distance);
+        while distance > 0 {
+            let (next,direction) = pos.neighbors(&self.elevations, rules)
+                .into_iter()
+                .find(|(next,_direction)| distances[next.index()] == Some(distance - 1)
+                    && rules.can_move(self.elevations[pos.index()], self.elevations[next.index()]))
+                .unwrap();
+            route.push((pos,direction));
+            pos = next;
+            distance -= 1;
+        }
+        Some(route)
+    }
+
+    //fewest steps to the end from a cell of elevation a, and the cells of elevation a which cannot reach it
+    fn fewest_steps_from_lowest(&self, distances: &Array2<Option<usize>>) -> (Option<usize>,Vec<Pos>) {
+        let (reachable,unreachable): (Vec<_>,Vec<_>) = self.elevations.indexed_iter()
+            .filter(|(_pos,&elevation)| elevation == 0)
+            .map(|((x,y),_elevation)| (Pos(x as i32,y as i32),distances[[x,y]]))
+            .partition(|(_pos,distance)| distance.is_some());
+        (reachable.iter().filter_map(|(_pos,distance)| *distance).min(),
+         unreachable.into_iter().map(|(pos,_distance)| pos).collect())
+    }
+
+    //draws the route over the heightmap like in the puzzle statement
+    fn render_route(&self, route: &[(Pos,Direction)]) -> String {
+        let mut drawing: Array2<char> = Array2::from_elem(self.elevations.raw_dim(), '.');
+        for (pos,direction) in route {
+            drawing[pos.index()] = direction.arrow();
+        }
+        drawing[self.end.index()] = 'E';
+        drawing.rows()
+            .into_iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl FromStr for Heightmap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let height = s.lines().count();
+        let width = s.lines().next().map_or(0, |line| line.len());
+        let mut start = None;
+        let mut end = None;
+        let mut elevations = Vec::with_capacity(height * width);
+        for (x,line) in s.lines().enumerate() {
+            if line.len() != width {
+                return Err(String::from("Every row of the heightmap must have the same length"));
+            }
+            for (y,c) in line.chars().enumerate() {
+                match c {
+                    'S' => start = Some(Pos(x as i32,y as i32)),
+                    'E' => end = Some(Pos(x as i32,y as i32)),
+                    'a'..='z' => {},
+                    c => return Err(format!("Invalid elevation {}", c))
+                }
+                elevations.push(convert_to_elevation(c));
+            }
+        }
+        Ok(Heightmap {
+            elevations: Array2::from_shape_vec((height,width), elevations).map_err(|e| e.to_string())?,
+            start: start.ok_or("Missing start")?,
+            end: end.ok_or("Missing end")?,
+        })
+    }
+}
+
 pub fn day12() {
     let mut file = File::open("./inputs/input_day12.txt").expect("File not found");
     let mut data = String::new();
     file.read_to_string(&mut data)
         .expect("Error while reading file");
 
-    let height = data.split('\n').count();
-    let width = data.split('\n').next().unwrap().len();
+    let heightmap = Heightmap::from_str(&data).unwrap();
+    let rules = ClimbingRules::default();
+    let distances = heightmap.distances_to_end(&rules);
 
-    let mut array: Array2<char> = Array2::default((height,width));
-    data.split('\n')
-        .zip(array.axis_iter_mut(Axis(0)))
-        .for_each(|(u,mut row)| u.chars().zip(row.iter_mut())
-            .for_each(|(c,elevation)| *elevation = c));
+    match heightmap.route(heightmap.start, &rules, &distances) {
+        Some(route) => {
+            println!("{}",heightmap.render_route(&route));
+            println!("Solution 1 : {:?}",route.len());
+        }
+        None => println!("Solution 1 : the end cannot be reached from the start")
+    }
 
-    let start = array.indexed_iter().find(|((_x,_y),c)| **c == 'S').unwrap();
-    let start = Pos(start.0.0 as i32, start.0.1 as i32);
+    let (min_length,unreachable) = heightmap.fewest_steps_from_lowest(&distances);
+    match min_length {
+        Some(min_length) => println!("Solution 2 : {:?}",min_length),
+        None => println!("Solution 2 : the end cannot be reached from any lowest cell")
+    }
+    println!("Lowest cells which cannot reach the end : {}",unreachable.len());
+}
 
-    let end = array.indexed_iter().find(|((_x,_y),c)| **c == 'E').unwrap();
-    let end: Pos = Pos(end.0.0 as i32, end.0.1 as i32);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let path = dijkstra(&start, |p| p.neighbors(&array), |p| *p == end).unwrap();
-    println!("Solution 1 : {:?}",path.0.len() - 1);
+    const EXAMPLE: &str = "Sabqponm
+abcryxxl
+accszExk
+acctuvwj
+abdefghi";
 
-    let min_length = array.indexed_iter()
-        .filter(|((_x,_y),c)| convert_to_elevation(**c) == 0)
-        .filter_map(|((x,y),_)| {
-            dijkstra(&Pos(x as i32,y as i32), |p| p.neighbors(&array), |p| *p == end)
-                .map(|v| v.0.len())
-        })
-        .min().unwrap() - 1;
+    //checks that every step of the route follows its direction and the rules, and that it ends next to the end
+    fn assert_valid_route(heightmap: &Heightmap, from: Pos, route: &[(Pos,Direction)], rules: &ClimbingRules) {
+        let mut pos = from;
+        for &(step,direction) in route {
+            assert_eq!(step, pos);
+            assert!(rules.directions().contains(&direction));
+            let (dx,dy) = direction.displacement();
+            let next = Pos(pos.0 + dx, pos.1 + dy);
+            assert!(rules.can_move(heightmap.elevations[pos.index()], heightmap.elevations[next.index()]));
+            pos = next;
+        }
+        assert_eq!(pos, heightmap.end);
+    }
 
+    #[test]
+    fn solve_example() {
+        let heightmap = Heightmap::from_str(EXAMPLE).unwrap();
+        let rules = ClimbingRules::default();
+        let distances = heightmap.distances_to_end(&rules);
+        assert_eq!(distances[heightmap.start.index()], Some(31));
+        assert_eq!(distances[heightmap.end.index()], Some(0));
+        let route = heightmap.route(heightmap.start, &rules, &distances).unwrap();
+        assert_eq!(route.len(), 31);
+        assert_valid_route(&heightmap, heightmap.start, &route, &rules);
+        assert_eq!(heightmap.render_route(&route), "\
+v..v<<<<
+>v.vv<<^
+.v.v>E^^
+.>v>>>^^
+..>>>>>^");
+        assert_eq!(heightmap.fewest_steps_from_lowest(&distances), (Some(29), vec![]));
+    }
+
+    #[test]
+    fn unreachable_start() {
+        //from the start, only b and c can be climbed, and both are too low to step up to y or z
+        let heightmap = Heightmap::from_str("SzE\nbcy").unwrap();
+        let rules = ClimbingRules::default();
+        let distances = heightmap.distances_to_end(&rules);
+        assert_eq!(distances[heightmap.start.index()], None);
+        assert_eq!(heightmap.route(heightmap.start, &rules, &distances), None);
+        assert_eq!(heightmap.fewest_steps_from_lowest(&distances), (None, vec![Pos(0,0)]));
+    }
 
-    println!("Solution 2 : {:?}",min_length);
+    #[test]
+    fn limited_descent() {
+        let rules = ClimbingRules { max_ascent: 25, max_descent: Some(1), diagonal_moves: false };
+        //going from c down to a is too steep, but a can still climb to the end
+        let heightmap = Heightmap::from_str("SdcaE").unwrap();
+        let distances = heightmap.distances_to_end(&rules);
+        assert_eq!(heightmap.route(heightmap.start, &rules, &distances), None);
+        assert_eq!(heightmap.fewest_steps_from_lowest(&distances), (Some(1), vec![Pos(0,0)]));
+        let distances = heightmap.distances_to_end(&ClimbingRules { max_descent: None, ..rules });
+        assert_eq!(distances[heightmap.start.index()], Some(4));
+        let heightmap = Heightmap::from_str("SdcbE").unwrap();
+        let distances = heightmap.distances_to_end(&rules);
+        let route = heightmap.route(heightmap.start, &rules, &distances).unwrap();
+        assert_valid_route(&heightmap, heightmap.start, &route, &rules);
+        assert_eq!(heightmap.render_route(&route), ">>>>E");
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn diagonal_moves() {
+        let rules = ClimbingRules { max_ascent: 25, max_descent: None, diagonal_moves: true };
+        let heightmap = Heightmap::from_str("Sb\nzE").unwrap();
+        let distances = heightmap.distances_to_end(&rules);
+        let route = heightmap.route(heightmap.start, &rules, &distances).unwrap();
+        assert_eq!(heightmap.render_route(&route), "↘.\n.E");
+        let heightmap = Heightmap::from_str(EXAMPLE).unwrap();
+        let rules = ClimbingRules { diagonal_moves: true, ..ClimbingRules::default() };
+        let distances = heightmap.distances_to_end(&rules);
+        let route = heightmap.route(heightmap.start, &rules, &distances).unwrap();
+        assert_valid_route(&heightmap, heightmap.start, &route, &rules);
+        assert!(route.len() < 31);
+        assert!(route.iter().any(|(_pos,direction)| DIAGONAL_DIRECTIONS.contains(direction)));
+    }
+}
@@ -1,6 +1,8 @@
 use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum ComparisonResult{
@@ -9,14 +11,43 @@ enum ComparisonResult{
     Continue,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-enum ListElement {
-    Int(u32),
-    List(Vec<ListElement>)
+//packets are equal when they compare equal, so [2] and [[2]] are the same packet
+#[derive(Debug, Clone)]
+enum ListElement<T> {
+    Int(T),
+    List(Vec<ListElement<T>>)
 }
 
-impl ListElement {
-    fn compare(&self, other: &ListElement) -> ComparisonResult {
+type Packet = ListElement<u64>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum JsonValue {
+    Null,
+    Bool(bool),
+    //kept as written, so that big integers are not rounded
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String,JsonValue)>),
+}
+
+//where and why two packets are first found to be different
+#[derive(Debug)]
+struct ComparisonExplanation {
+    result: ComparisonResult,
+    path: Vec<usize>,
+    reason: String,
+    conversions: Vec<String>,
+}
+
+//reads a packet in a single pass, errors give the position of the faulty character
+struct PacketParser<'a> {
+    chars: &'a [u8],
+    position: usize,
+}
+
+impl<T: Ord> ListElement<T> {
+    fn compare(&self, other: &ListElement<T>) -> ComparisonResult {
         match (self,other) {
             (ListElement::Int(v1), ListElement::Int(v2)) => {
                 match v1.cmp(v2) {
@@ -25,98 +56,582 @@ impl ListElement {
                     Ordering::Greater => ComparisonResult::WrongOrder
                 }
             }
-            (ListElement::Int(v1), ListElement::List(l2)) => {
-                ListElement::List(vec![ListElement::Int(*v1)]).compare(&ListElement::List(l2.clone()))
+            //an integer is compared as a list holding only itself
+            (ListElement::Int(_), ListElement::List(l2)) => compare_lists(std::slice::from_ref(self), l2),
+            (ListElement::List(l1),ListElement::Int(_)) => compare_lists(l1, std::slice::from_ref(other)),
+            (ListElement::List(l1),ListElement::List(l2)) => compare_lists(l1, l2),
+        }
+    }
+}
+
+impl<T> ListElement<T> {
+    //an integer has depth 0, a list is one deeper than its deepest element
+    fn depth(&self) -> usize {
+        match self {
+            ListElement::Int(_) => 0,
+            ListElement::List(l) => 1 + l.iter().map(|le| le.depth()).max().unwrap_or(0),
+        }
+    }
+
+    //number of integers in the packet
+    fn size(&self) -> usize {
+        match self {
+            ListElement::Int(_) => 1,
+            ListElement::List(l) => l.iter().map(|le| le.size()).sum(),
+        }
+    }
+
+    fn flatten(&self) -> Vec<&T> {
+        match self {
+            ListElement::Int(v) => vec![v],
+            ListElement::List(l) => l.iter().flat_map(|le| le.flatten()).collect(),
+        }
+    }
+
+    fn to_json(&self) -> JsonValue where T: Display {
+        match self {
+            ListElement::Int(v) => JsonValue::Number(v.to_string()),
+            ListElement::List(l) => JsonValue::Array(l.iter().map(|le| le.to_json()).collect()),
+        }
+    }
+}
+
+impl<T: Ord + Display> ListElement<T> {
+    fn explain_compare(&self, other: &ListElement<T>) -> ComparisonExplanation {
+        let mut explanation = ComparisonExplanation {
+            result: ComparisonResult::Continue,
+            path: Vec::new(),
+            reason: String::from("the packets are equal"),
+            conversions: Vec::new(),
+        };
+        explain_elements(self, other, &mut explanation);
+        explanation
+    }
+}
+
+fn format_path(path: &[usize]) -> String {
+    path.iter().map(|i| format!("[{}]", i)).collect()
+}
+
+//fills the explanation and returns true when a difference is found
+fn explain_elements<T: Ord + Display>(left: &ListElement<T>, right: &ListElement<T>, explanation: &mut ComparisonExplanation) -> bool {
+    match (left, right) {
+        (ListElement::Int(v1), ListElement::Int(v2)) => {
+            explanation.result = left.compare(right);
+            explanation.reason = match explanation.result {
+                ComparisonResult::RightOrder => format!("left integer {} is smaller than right integer {}", v1, v2),
+                ComparisonResult::WrongOrder => format!("left integer {} is greater than right integer {}", v1, v2),
+                ComparisonResult::Continue => return false,
+            };
+            true
+        }
+        (ListElement::Int(v1), ListElement::List(l2)) => {
+            explanation.conversions.push(format!("left {} converted to [{}] at {}", v1, v1, format_path(&explanation.path)));
+            explain_lists(std::slice::from_ref(left), l2, explanation)
+        }
+        (ListElement::List(l1), ListElement::Int(v2)) => {
+            explanation.conversions.push(format!("right {} converted to [{}] at {}", v2, v2, format_path(&explanation.path)));
+            explain_lists(l1, std::slice::from_ref(right), explanation)
+        }
+        (ListElement::List(l1), ListElement::List(l2)) => explain_lists(l1, l2, explanation),
+    }
+}
+
+fn explain_lists<T: Ord + Display>(l1: &[ListElement<T>], l2: &[ListElement<T>], explanation: &mut ComparisonExplanation) -> bool {
+    for i in 0..l1.len().max(l2.len()) {
+        explanation.path.push(i);
+        match (l1.get(i), l2.get(i)) {
+            (Some(le1), Some(le2)) => {
+                if explain_elements(le1, le2, explanation) {
+                    return true;
+                }
+            }
+            (Some(_), None) => {
+                explanation.result = ComparisonResult::WrongOrder;
+                explanation.reason = String::from("right list ran out of items first");
+                return true;
             }
-            (ListElement::List(l1),ListElement::Int(v2)) => {
-                ListElement::List(l1.clone()).compare(&ListElement::List(vec![ListElement::Int(*v2)]))
+            (None, _) => {
+                explanation.result = ComparisonResult::RightOrder;
+                explanation.reason = String::from("left list ran out of items first");
+                return true;
             }
-            (ListElement::List(l1),ListElement::List(l2)) => {
-                let mut it1 = l1.iter();
-                let mut it2 = l2.iter();
-                loop {
-                    let option_le1 = it1.next();
-                    let option_le2 = it2.next();
-                    match (option_le1, option_le2) {
-                        (Some(le1), Some(le2)) => {
-                            let result = le1.compare(le2);
-                            if result != ComparisonResult::Continue {
-                                return result;
-                            }
-                        },
-                        (Some(_),None) => return ComparisonResult::WrongOrder,
-                        (None,Some(_)) => return ComparisonResult::RightOrder,
-                        (None,None) => return ComparisonResult::Continue,
-                    }
+        }
+        explanation.path.pop();
+    }
+    false
+}
+
+impl Display for ComparisonExplanation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:?} at {} : {}", self.result, format_path(&self.path), self.reason)?;
+        for conversion in self.conversions.iter() {
+            writeln!(f, "  {}", conversion)?;
+        }
+        Ok(())
+    }
+}
+
+fn compare_lists<T: Ord>(l1: &[ListElement<T>], l2: &[ListElement<T>]) -> ComparisonResult {
+    let mut it1 = l1.iter();
+    let mut it2 = l2.iter();
+    loop {
+        let option_le1 = it1.next();
+        let option_le2 = it2.next();
+        match (option_le1, option_le2) {
+            (Some(le1), Some(le2)) => {
+                let result = le1.compare(le2);
+                if result != ComparisonResult::Continue {
+                    return result;
                 }
-            }
+            },
+            (Some(_),None) => return ComparisonResult::WrongOrder,
+            (None,Some(_)) => return ComparisonResult::RightOrder,
+            (None,None) => return ComparisonResult::Continue,
         }
     }
 }
 
-impl PartialOrd<Self> for ListElement {
+impl<T: Ord> PartialEq for ListElement<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.compare(other) == ComparisonResult::Continue
+    }
+}
+
+impl<T: Ord> Eq for ListElement<T> {}
+
+impl<T: Ord> PartialOrd<Self> for ListElement<T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(match self.compare(other) {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for ListElement<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.compare(other) {
             ComparisonResult::RightOrder => Ordering::Less,
             ComparisonResult::WrongOrder => Ordering::Greater,
             ComparisonResult::Continue => Ordering::Equal,
-        })
+        }
     }
 }
 
-impl Ord for ListElement {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
-    }
-}
-fn split_by_correct_commas(s: &str) -> Vec<String> {
-    let mut result: Vec<String> = Vec::new();
-    let mut nb_opened_brackets = 0;
-    let mut buffer = String::new();
-    s.chars()
-        .for_each(|c| {
-            match c {
-                '[' => {
-                    nb_opened_brackets += 1;
-                    buffer.push(c) ;
+//writes the packet back in its canonical form, without spaces
+impl<T: Display> Display for ListElement<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListElement::Int(v) => write!(f, "{}", v),
+            ListElement::List(l) => {
+                write!(f, "[")?;
+                for (i, le) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", le)?;
                 }
-                ']' => {
-                    nb_opened_brackets -= 1;
-                    buffer.push(c) ;
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+//writes the string between quotes, with the escapes of the JSON grammar
+fn write_json_string(f: &mut Formatter<'_>, s: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            '\u{8}' => write!(f, "\\b")?,
+            '\u{c}' => write!(f, "\\f")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl Display for JsonValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(b) => write!(f, "{}", b),
+            JsonValue::Number(n) => write!(f, "{}", n),
+            JsonValue::String(s) => write_json_string(f, s),
+            JsonValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
                 }
-                ',' => {
-                    if nb_opened_brackets == 0 {
-                        result.push(buffer.clone());
-                        buffer = String::new();
+                write!(f, "]")
+            }
+            JsonValue::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
                     }
-                    else{
-                        buffer.push(c);
+                    write_json_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+//the digits of the integer a JSON number is equal to, such as 100 for 1e2 or 15 for 1.50e1,
+//None when the number has a fractional part or more digits than any integer type holds
+fn integer_value(number: &str) -> Option<String> {
+    let (mantissa,exponent) = match number.split_once(['e','E']) {
+        Some((mantissa,exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+        None => (number, 0),
+    };
+    let (sign,mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let (integer_part,fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", integer_part, fraction);
+    let exponent = exponent.saturating_sub(fraction.len() as i64);
+    let digits = if exponent >= 0 {
+        if digits.len() as i64 + exponent > 64 {
+            return None;
+        }
+        digits + &"0".repeat(exponent as usize)
+    }
+    else {
+        let kept = digits.len().saturating_sub(exponent.unsigned_abs() as usize);
+        if digits[kept..].bytes().any(|c| c != b'0') {
+            return None;
+        }
+        digits[..kept].to_string()
+    };
+    let digits = digits.trim_start_matches('0');
+    Some(if digits.is_empty() { String::from("0") } else { format!("{}{}", sign, digits) })
+}
+
+//only arrays of non-negative integers are packets
+impl<T: FromStr> TryFrom<&JsonValue> for ListElement<T> {
+    type Error = String;
+
+    fn try_from(value: &JsonValue) -> Result<Self, Self::Error> {
+        match value {
+            JsonValue::Number(n) => integer_value(n)
+                .and_then(|integer| integer.parse::<T>().ok())
+                .map(ListElement::Int)
+                .ok_or(format!("{} is not a packet integer", n)),
+            JsonValue::Array(values) => values.iter()
+                .map(ListElement::try_from)
+                .collect::<Result<Vec<ListElement<T>>,String>>()
+                .map(ListElement::List),
+            other => Err(format!("{} is not a packet value", other)),
+        }
+    }
+}
+
+impl<'a> PacketParser<'a> {
+    fn new(s: &'a str) -> Self {
+        PacketParser { chars: s.as_bytes(), position: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn error(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(c) => format!("Expected {} at position {}, found {:?}", expected, self.position, c as char),
+            None => format!("Expected {} at position {}, found the end of the input", expected, self.position),
+        }
+    }
+
+    fn parse_element<T: FromStr>(&mut self) -> Result<ListElement<T>,String> {
+        match self.peek() {
+            Some(b'[') => {
+                self.position += 1;
+                let mut l = Vec::new();
+                if self.peek() == Some(b']') {
+                    self.position += 1;
+                    return Ok(ListElement::List(l));
+                }
+                loop {
+                    l.push(self.parse_element()?);
+                    match self.peek() {
+                        Some(b',') => self.position += 1,
+                        Some(b']') => {
+                            self.position += 1;
+                            return Ok(ListElement::List(l));
+                        }
+                        _ => return Err(self.error("',' or ']'"))
                     }
                 }
-                c => {
-                    buffer.push(c) ;
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.position;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.position += 1;
+                }
+                std::str::from_utf8(&self.chars[start..self.position]).unwrap()
+                    .parse::<T>()
+                    .map(ListElement::Int)
+                    .map_err(|_| format!("Invalid integer at position {}", start))
+            }
+            _ => Err(self.error("'[' or an integer"))
+        }
+    }
+}
+
+impl PacketParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    //moves past the digits and returns how many there were
+    fn skip_digits(&mut self) -> usize {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    //an optional minus, an integer part without leading zeros, then optional fraction and exponent parts
+    fn parse_json_number(&mut self) -> Result<JsonValue,String> {
+        let start = self.position;
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.position += 1,
+            _ => {
+                if self.skip_digits() == 0 {
+                    return Err(self.error("a digit"));
+                }
+            }
+        }
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            if self.skip_digits() == 0 {
+                return Err(self.error("a digit after the decimal point"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.position += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.position += 1;
+            }
+            if self.skip_digits() == 0 {
+                return Err(self.error("a digit in the exponent"));
+            }
+        }
+        Ok(JsonValue::Number(std::str::from_utf8(&self.chars[start..self.position]).unwrap().to_string()))
+    }
+
+    fn expect_word(&mut self, word: &str, value: JsonValue) -> Result<JsonValue,String> {
+        if self.chars[self.position..].starts_with(word.as_bytes()) {
+            self.position += word.len();
+            Ok(value)
+        }
+        else {
+            Err(self.error(word))
+        }
+    }
+
+    //reads the 4 hexadecimal digits following the u of an escape, and stops on the last one
+    fn parse_unicode_escape(&mut self) -> Result<u32,String> {
+        let digits = self.chars.get(self.position + 1..self.position + 5)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .filter(|digits| digits.bytes().all(|c| c.is_ascii_hexdigit()))
+            .ok_or(self.error("4 hexadecimal digits after \\u"))?;
+        self.position += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+
+    fn parse_json_string(&mut self) -> Result<String,String> {
+        self.position += 1;
+        let mut result = String::new();
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(result);
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    let escaped = match self.peek() {
+                        Some(b'n') => '\n',
+                        Some(b't') => '\t',
+                        Some(b'r') => '\r',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(c @ (b'"' | b'\\' | b'/')) => c as char,
+                        Some(b'u') => {
+                            let code = self.parse_unicode_escape()?;
+                            //characters outside the basic plane are written as a pair of surrogates
+                            let code = if (0xD800..0xDC00).contains(&code) {
+                                if self.chars.get(self.position + 1..self.position + 3) != Some(b"\\u") {
+                                    return Err(self.error("a low surrogate"));
+                                }
+                                self.position += 2;
+                                let low = self.parse_unicode_escape()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(self.error("a low surrogate"));
+                                }
+                                0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+                            }
+                            else {
+                                code
+                            };
+                            char::from_u32(code).ok_or(self.error("a valid character code"))?
+                        }
+                        _ => return Err(self.error("an escaped character")),
+                    };
+                    result.push(escaped);
+                    self.position += 1;
+                }
+                Some(c) if c < 0x20 => return Err(self.error("an escaped control character")),
+                Some(c) => {
+                    let width = match c {
+                        0x00..=0x7f => 1,
+                        0xc0..=0xdf => 2,
+                        0xe0..=0xef => 3,
+                        _ => 4,
+                    };
+                    let bytes = self.chars.get(self.position..self.position + width).ok_or(self.error("a character"))?;
+                    result.push_str(std::str::from_utf8(bytes).map_err(|e| e.to_string())?);
+                    self.position += width;
                 }
+                None => return Err(self.error("'\"'")),
             }
-        });
-    if !buffer.is_empty() {
-        result.push(buffer.clone());
+        }
+    }
+
+    fn parse_json(&mut self) -> Result<JsonValue,String> {
+        self.skip_whitespace();
+        let value = match self.peek() {
+            Some(b'n') => self.expect_word("null", JsonValue::Null),
+            Some(b't') => self.expect_word("true", JsonValue::Bool(true)),
+            Some(b'f') => self.expect_word("false", JsonValue::Bool(false)),
+            Some(b'"') => self.parse_json_string().map(JsonValue::String),
+            Some(c) if c == b'-' || c.is_ascii_digit() => self.parse_json_number(),
+            Some(open @ (b'[' | b'{')) => {
+                self.position += 1;
+                let close = if open == b'[' { b']' } else { b'}' };
+                let mut values = Vec::new();
+                let mut entries = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(close) {
+                    self.position += 1;
+                }
+                else {
+                    loop {
+                        if open == b'[' {
+                            values.push(self.parse_json()?);
+                        }
+                        else {
+                            self.skip_whitespace();
+                            if self.peek() != Some(b'"') {
+                                return Err(self.error("a key"));
+                            }
+                            let key = self.parse_json_string()?;
+                            self.skip_whitespace();
+                            if self.peek() != Some(b':') {
+                                return Err(self.error("':'"));
+                            }
+                            self.position += 1;
+                            entries.push((key, self.parse_json()?));
+                        }
+                        self.skip_whitespace();
+                        match self.peek() {
+                            Some(b',') => self.position += 1,
+                            Some(c) if c == close => {
+                                self.position += 1;
+                                break;
+                            }
+                            _ => return Err(self.error("',' or the end of the collection")),
+                        }
+                    }
+                }
+                Ok(if open == b'[' { JsonValue::Array(values) } else { JsonValue::Object(entries) })
+            }
+            _ => Err(self.error("a JSON value")),
+        };
+        self.skip_whitespace();
+        value
+    }
+}
+
+impl FromStr for JsonValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = PacketParser::new(s);
+        let value = parser.parse_json()?;
+        match parser.peek() {
+            None => Ok(value),
+            Some(_) => Err(parser.error("the end of the JSON value"))
+        }
     }
+}
+
+impl<T: FromStr> FromStr for ListElement<T> {
+    type Err = String;
 
-    result
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = PacketParser::new(s.trim());
+        let le = parser.parse_element()?;
+        match parser.peek() {
+            None => Ok(le),
+            Some(_) => Err(parser.error("the end of the packet"))
+        }
+    }
 }
-fn parse(s: &str) -> ListElement {
-    if s.starts_with('[') {
-        //last char must be ]
-        //first : find the correct commas
-        let v: Vec<ListElement> = split_by_correct_commas(&s[1..s.len()-1])
-            .iter()
-            .map(|s| parse(s))
-            .collect();
-        ListElement::List(v)
-    } else {
-        let nb = s.parse::<u32>().unwrap();
-        ListElement::Int(nb)
+
+//1-based ranks the dividers would have once added after the packets and stably sorted, found in a single pass
+//by counting the packets smaller than or equal to every divider, equal dividers are ranked in their given order
+fn divider_ranks<T: Ord>(packets: &[ListElement<T>], dividers: &[ListElement<T>]) -> Vec<usize> {
+    let mut ranks = dividers.iter()
+        .enumerate()
+        .map(|(i,divider)| 1 + dividers.iter()
+            .enumerate()
+            .filter(|&(j,other)| other < divider || (other == divider && j < i))
+            .count())
+        .collect::<Vec<usize>>();
+    for packet in packets.iter() {
+        for (rank,divider) in ranks.iter_mut().zip(dividers.iter()) {
+            if packet <= divider {
+                *rank += 1;
+            }
+        }
     }
+    ranks
+}
+
+//every packet and divider in order, one per line, equal packets keeping their input order and dividers coming after them
+#[cfg(test)]
+fn sorted_listing<T: Ord + Display>(packets: &[ListElement<T>], dividers: &[ListElement<T>]) -> String {
+    let mut all = packets.iter().chain(dividers.iter()).collect::<Vec<&ListElement<T>>>();
+    all.sort();
+    all.iter()
+        .map(|le| le.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+fn write_sorted_listing<T: Ord + Display>(packets: &[ListElement<T>], dividers: &[ListElement<T>], path: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut file = File::create(path)?;
+    writeln!(file, "{}", sorted_listing(packets, dividers))
 }
 
 pub fn day13() {
@@ -125,33 +640,218 @@ pub fn day13() {
     file.read_to_string(&mut data)
         .expect("Error while reading file");
 
-    let sol1 = data.split("\n\n")
-        .enumerate()
-        .filter_map(|(i,pair)| {
+    let pairs = data.split("\n\n")
+        .map(|pair| {
             let mut iter_pair = pair.split('\n');
-            let le1 = parse(iter_pair.next().unwrap());
-            let le2 = parse(iter_pair.next().unwrap());
-            match le1.compare(&le2) {
+            let le1 = Packet::from_str(iter_pair.next().unwrap()).unwrap();
+            let le2 = Packet::from_str(iter_pair.next().unwrap()).unwrap();
+            (le1,le2)
+        })
+        .collect::<Vec<(Packet,Packet)>>();
+    let sol1 = pairs.iter()
+        .enumerate()
+        .filter_map(|(i,(le1,le2))| {
+            match le1.compare(le2) {
                 ComparisonResult::RightOrder => Some(i+1),
                 _ => None,
             }
         })
         .sum::<usize>();
 
-    let mut v = data
+    let v = data
         .split_whitespace()
-        .map(parse)
-        .collect::<Vec<ListElement>>();
+        .map(|s| Packet::from_str(s).unwrap())
+        .collect::<Vec<Packet>>();
 
-    let le2 = parse("[[2]]");
-    let le6 = parse("[[6]]");
+    let dividers = ["[[2]]","[[6]]"].map(|s| Packet::from_str(s).unwrap());
+    let ranks = divider_ranks(&v, &dividers);
+    println!("Solution 1 : {}",sol1);
+    println!("Solution 2 : {:?}",ranks.iter().product::<usize>());
 
-    v.push(le2.clone());
-    v.push(le6.clone());
-    v.sort_unstable();
+    if let Some((i,(le1,le2))) = pairs.iter().enumerate().find(|(_i,(le1,le2))| le1 > le2) {
+        print!("First pair in the wrong order ({}) : {}", i+1, le1.explain_compare(le2));
+    }
+    if let Some(deepest) = v.iter().max_by_key(|le| le.depth()) {
+        println!("Deepest packet ({}) : {}", deepest.depth(), deepest.to_json());
+    }
+    println!("Integers in all packets : {}", v.iter().map(|le| le.size()).sum::<usize>());
+    println!("Largest integer : {:?}", v.iter().flat_map(|le| le.flatten()).max());
+}
 
-    let p2 = v.iter().position(|le| *le == le2).unwrap() + 1;
-    let p6 = v.iter().position(|le| *le == le6).unwrap() + 1;
-    println!("Solution 1 : {}",sol1);
-    println!("Solution 2 : {:?}",p2*p6);
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "[1,1,3,1,1]
+[1,1,5,1,1]
+
+[[1],[2,3,4]]
+[[1],4]
+
+[9]
+[[8,7,6]]
+
+[[4,4],4,4]
+[[4,4],4,4,4]
+
+[7,7,7,7]
+[7,7,7]
+
+[]
+[3]
+
+[[[]]]
+[[]]
+
+[1,[2,[3,[4,[5,6,7]]]],8,9]
+[1,[2,[3,[4,[5,6,0]]]],8,9]";
+
+    fn packets(data: &str) -> Vec<Packet> {
+        data.split_whitespace().map(|s| Packet::from_str(s).unwrap()).collect()
+    }
+
+    fn dividers() -> [Packet; 2] {
+        ["[[2]]","[[6]]"].map(|s| Packet::from_str(s).unwrap())
+    }
+
+    #[test]
+    fn round_trips_on_example() {
+        for s in EXAMPLE.split_whitespace() {
+            let packet = Packet::from_str(s).unwrap();
+            assert_eq!(packet.to_string(), s);
+            assert_eq!(Packet::try_from(&JsonValue::from_str(&packet.to_json().to_string()).unwrap()).unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn equality_follows_the_order() {
+        let (a,b) = (Packet::from_str("[2]").unwrap(), Packet::from_str("[[2]]").unwrap());
+        assert_eq!(a.cmp(&b), Ordering::Equal);
+        assert_eq!(a, b);
+        assert_ne!(a, Packet::from_str("[2,2]").unwrap());
+    }
+
+    #[test]
+    fn divider_ranks_on_example() {
+        assert_eq!(divider_ranks(&packets(EXAMPLE), &dividers()), vec![10, 14]);
+    }
+
+    #[test]
+    fn divider_ranks_match_listing_with_ties() {
+        let data = format!("{}\n\n[2]\n[3]\n\n[[6]]\n[6]", EXAMPLE);
+        let packets = packets(&data);
+        let ranks = divider_ranks(&packets, &dividers());
+        assert_eq!(ranks, vec![11, 18]);
+        let listing = sorted_listing(&packets, &dividers());
+        let lines = listing.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[9], "[2]");
+        for (rank,divider) in ranks.iter().zip(dividers().iter()) {
+            assert_eq!(lines[rank - 1], divider.to_string());
+        }
+    }
+
+    #[test]
+    fn write_listing_to_file() {
+        let path = std::env::temp_dir().join("day13_sorted_test.txt");
+        let path = path.to_string_lossy();
+        write_sorted_listing(&packets(EXAMPLE), &dividers(), &path).unwrap();
+        let written = std::fs::read_to_string(path.as_ref()).unwrap();
+        std::fs::remove_file(path.as_ref()).unwrap();
+        let lines = written.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 18);
+        assert_eq!((lines[0], lines[9], lines[13], lines[17]), ("[]", "[[2]]", "[[6]]", "[9]"));
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        let value = JsonValue::String(String::from("a\"b\\c\n\t\r\u{8}\u{c}\u{0}\u{1f}\u{7f}é😀"));
+        assert_eq!(value.to_string(), "\"a\\\"b\\\\c\\n\\t\\r\\b\\f\\u0000\\u001f\u{7f}é😀\"");
+        assert_eq!(JsonValue::from_str(&value.to_string()), Ok(value));
+    }
+
+    #[test]
+    fn parse_unicode_escapes() {
+        assert_eq!(JsonValue::from_str("\"\\u0041\\u00e9\\/\""), Ok(JsonValue::String(String::from("Aé/"))));
+        assert_eq!(JsonValue::from_str("\"\\ud83d\\ude00\""), Ok(JsonValue::String(String::from("😀"))));
+        assert!(JsonValue::from_str("\"\\ud83d\"").is_err());
+        assert!(JsonValue::from_str("\"\\ud83d\\u0041\"").is_err());
+        assert!(JsonValue::from_str("\"\\u00g1\"").is_err());
+        assert!(JsonValue::from_str("\"a\u{1}\"").is_err());
+    }
+
+    #[test]
+    fn json_objects_and_packets() {
+        let value = JsonValue::from_str(" {\"k\\ty\" : [1, [2, true, null]], \"\" : \"\\u00e9\"} ").unwrap();
+        assert_eq!(value.to_string(), "{\"k\\ty\":[1,[2,true,null]],\"\":\"é\"}");
+        let packet = Packet::from_str("[1,[2,[3,[4,[5,6,7]]]],8,9]").unwrap();
+        assert_eq!(packet.to_json().to_string(), "[1,[2,[3,[4,[5,6,7]]]],8,9]");
+        assert_eq!(Packet::try_from(&JsonValue::from_str("[1, [ ], [2]]").unwrap()).unwrap().to_string(), "[1,[],[2]]");
+        assert!(Packet::try_from(&JsonValue::from_str("[1,\"a\"]").unwrap()).is_err());
+        assert!(Packet::try_from(&JsonValue::from_str("[-1]").unwrap()).is_err());
+    }
+
+    #[test]
+    fn json_numbers() {
+        for number in ["0", "-0", "12", "-3.25", "1e2", "1.5E+3", "2e-1", "0.0"] {
+            assert_eq!(JsonValue::from_str(number), Ok(JsonValue::Number(String::from(number))));
+        }
+        for number in ["01", "1.", ".5", "-", "1e", "1e+", "+1", "--1", "1.2.3"] {
+            assert!(JsonValue::from_str(number).is_err(), "{}", number);
+        }
+        assert_eq!(JsonValue::from_str("[1,]"), Err(String::from("Expected a JSON value at position 3, found ']'")));
+        assert_eq!(JsonValue::from_str("[1"), Err(String::from("Expected ',' or the end of the collection at position 2, found the end of the input")));
+    }
+
+    #[test]
+    fn json_numbers_as_packet_integers() {
+        let packet = |json: &str| Packet::try_from(&JsonValue::from_str(json).unwrap()).map(|packet| packet.to_string());
+        assert_eq!(packet("[1e2, 1.50e1, 7.0, 2000e-3, 0e-5, -0]"), Ok(String::from("[100,15,7,2,0,0]")));
+        assert_eq!(packet("[0.5]"), Err(String::from("0.5 is not a packet integer")));
+        assert_eq!(packet("[15e-1]"), Err(String::from("15e-1 is not a packet integer")));
+        assert_eq!(packet("[1e100]"), Err(String::from("1e100 is not a packet integer")));
+        assert_eq!(packet("[-1e2]"), Err(String::from("-1e2 is not a packet integer")));
+    }
+
+    #[test]
+    fn explain_example_pairs() {
+        let explain = |left: &str, right: &str| {
+            let explanation = Packet::from_str(left).unwrap().explain_compare(&Packet::from_str(right).unwrap());
+            (explanation.result, format_path(&explanation.path), explanation.reason, explanation.conversions)
+        };
+        assert_eq!(explain("[1,1,3,1,1]", "[1,1,5,1,1]"),
+                   (ComparisonResult::RightOrder, String::from("[2]"), String::from("left integer 3 is smaller than right integer 5"), vec![]));
+        assert_eq!(explain("[[1],[2,3,4]]", "[[1],4]"),
+                   (ComparisonResult::RightOrder, String::from("[1][0]"), String::from("left integer 2 is smaller than right integer 4"),
+                    vec![String::from("right 4 converted to [4] at [1]")]));
+        assert_eq!(explain("[9]", "[[8,7,6]]"),
+                   (ComparisonResult::WrongOrder, String::from("[0][0]"), String::from("left integer 9 is greater than right integer 8"),
+                    vec![String::from("left 9 converted to [9] at [0]")]));
+        assert_eq!(explain("[[4,4],4,4]", "[[4,4],4,4,4]"),
+                   (ComparisonResult::RightOrder, String::from("[3]"), String::from("left list ran out of items first"), vec![]));
+        assert_eq!(explain("[7,7,7,7]", "[7,7,7]"),
+                   (ComparisonResult::WrongOrder, String::from("[3]"), String::from("right list ran out of items first"), vec![]));
+        assert_eq!(explain("[[[]]]", "[[]]"),
+                   (ComparisonResult::WrongOrder, String::from("[0][0]"), String::from("right list ran out of items first"), vec![]));
+        assert_eq!(explain("[2]", "[[2]]"),
+                   (ComparisonResult::Continue, String::new(), String::from("the packets are equal"),
+                    vec![String::from("left 2 converted to [2] at [0]")]));
+    }
+
+    #[test]
+    fn explanation_display() {
+        let explanation = Packet::from_str("[[1],[2,3,4]]").unwrap().explain_compare(&Packet::from_str("[[1],4]").unwrap());
+        assert_eq!(explanation.to_string(), "RightOrder at [1][0] : left integer 2 is smaller than right integer 4\n  right 4 converted to [4] at [1]\n");
+    }
+
+    #[test]
+    fn structural_metrics() {
+        let packet = Packet::from_str("[1,[2,[3,[4,[5,6,7]]]],8,9]").unwrap();
+        assert_eq!((packet.depth(), packet.size()), (5, 9));
+        assert_eq!(packet.flatten(), (1..=9).collect::<Vec<u64>>().iter().collect::<Vec<&u64>>());
+        let packet = Packet::from_str("[[[]],[]]").unwrap();
+        assert_eq!((packet.depth(), packet.size()), (3, 0));
+        assert!(packet.flatten().is_empty());
+        let packet = Packet::from_str("7").unwrap();
+        assert_eq!((packet.depth(), packet.size(), packet.flatten()), (0, 1, vec![&7]));
+    }
+}
@@ -1,101 +1,359 @@
-use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 use itertools::Itertools;
+use ndarray::{Array2, Dimension};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Cell {
+    Air,
+    Rock,
+    Sand,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum FloorBehaviour {
+    //sand falling below the lowest rock is lost
+    Abyss,
+    //endless floor, this many rows below the lowest rock
+    Floor(i32),
+    //floor this many rows below the lowest rock, closed by walls on each side of the rocks
+    Walls(i32),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum SandSolver {
+    //drops every grain
+    Simulation,
+    //counts the cells the sources can reach row by row, only right when no grain can be lost
+    Reachability,
+}
+
+#[derive(Debug)]
+enum GrainFate {
+    Settled,
+    Lost,
+    SourceBlocked,
+}
+
+#[derive(Debug)]
+struct Source {
+    pos: (i32,i32),
+    //cells the last grain went through, the next grain resumes from the last one which is still free
+    path: Vec<[usize; 2]>,
+}
+
+//cells are indexed by [y, x - min_x]
+struct Cave {
+    grid: Array2<Cell>,
+    min_x: i32,
+    sources: Vec<Source>,
+    floor: FloorBehaviour,
+}
 
 fn get_line(p1: (i32,i32), p2: (i32,i32)) -> Vec<(i32,i32)> {
     if p1.0 == p2.0 {
-        let mut vy = vec![p1.1,p2.1];
+        let mut vy = [p1.1,p2.1];
         vy.sort_unstable();
         (vy[0]..=vy[1]).map(|y| (p1.0,y)).collect()
     }
     else {
-        let mut vx = vec![p1.0,p2.0];
+        let mut vx = [p1.0,p2.0];
         vx.sort_unstable();
         (vx[0]..=vx[1]).map(|x| (x,p1.1)).collect()
     }
 }
 
-fn put_sand_and_check_abyss(cave: &mut HashSet<(i32,i32)>, abyss_height: i32) -> bool {
-    let (mut x, mut y) = (500,0);
-    loop {
-        if y >= abyss_height {
-            return true
+fn parse_paths(s: &str) -> Result<Vec<Vec<(i32,i32)>>,String> {
+    s.lines()
+        .map(|line| line.split(" -> ")
+            .map(|pair| {
+                let (x,y) = pair.split_once(',').ok_or(format!("Invalid point {}", pair))?;
+                Ok((x.parse::<i32>().map_err(|e| e.to_string())?, y.parse::<i32>().map_err(|e| e.to_string())?))
+            })
+            .collect::<Result<Vec<(i32,i32)>,String>>())
+        .collect()
+}
+
+impl Cave {
+    fn new(paths: &[Vec<(i32,i32)>], sources: &[(i32,i32)], floor: FloorBehaviour) -> Result<Self,String> {
+        let rocks = paths.iter()
+            .flat_map(|path| path.iter()
+                .tuple_windows::<(_,_)>()
+                .flat_map(|(&p1,&p2)| get_line(p1,p2)))
+            .collect::<Vec<(i32,i32)>>();
+        let points = || rocks.iter().chain(sources.iter());
+        let max_y = points().map(|p| p.1).max().ok_or("The cave has neither rocks nor sources")?;
+        let (mut min_x,mut max_x) = points().map(|p| p.0).minmax().into_option().unwrap();
+        if let Some(&(x,y)) = points().find(|p| p.1 < 0) {
+            return Err(format!("({},{}) is above the top of the cave", x, y));
         }
-        else if !cave.contains(&(x,y+1)) {
-            y += 1;
+        if let FloorBehaviour::Floor(offset) | FloorBehaviour::Walls(offset) = floor {
+            if offset < 1 {
+                return Err(format!("The floor must be at least 1 row below the lowest rock, not {}", offset));
+            }
         }
-        else if !cave.contains(&(x-1,y+1)) {
-            x -= 1;
-            y += 1;
+        let height = match floor {
+            FloorBehaviour::Abyss => max_y + 1,
+            FloorBehaviour::Floor(offset) | FloorBehaviour::Walls(offset) => max_y + offset + 1,
+        };
+        match floor {
+            FloorBehaviour::Abyss => {}
+            //sand can only spread in the triangle below every source
+            FloorBehaviour::Floor(_) => {
+                for &(x,y) in sources {
+                    min_x = min_x.min(x - (height - y));
+                    max_x = max_x.max(x + (height - y));
+                }
+            }
+            FloorBehaviour::Walls(_) => {
+                min_x -= 1;
+                max_x += 1;
+            }
         }
-        else if !cave.contains(&(x+1,y+1)) {
-            x += 1;
-            y += 1;
+        let mut grid = Array2::from_elem((height as usize, (max_x - min_x + 1) as usize), Cell::Air);
+        for &(x,y) in rocks.iter() {
+            if y < height {
+                grid[[y as usize, (x - min_x) as usize]] = Cell::Rock;
+            }
         }
-        else {
-            cave.insert((x,y));
-            return false
+        if let FloorBehaviour::Floor(_) | FloorBehaviour::Walls(_) = floor {
+            grid.row_mut(height as usize - 1).fill(Cell::Rock);
         }
+        if let FloorBehaviour::Walls(_) = floor {
+            grid.column_mut(0).fill(Cell::Rock);
+            grid.column_mut((max_x - min_x) as usize).fill(Cell::Rock);
+        }
+        let sources = sources.iter()
+            .map(|&(x,y)| {
+                let index = [y as usize, (x - min_x) as usize];
+                if grid.get(index).is_none() {
+                    return Err(format!("Source ({},{}) is below the floor", x, y));
+                }
+                let path = if grid[index] == Cell::Air { vec![index] } else { Vec::new() };
+                Ok(Source { pos: (x,y), path })
+            })
+            .collect::<Result<Vec<Source>,String>>()?;
+        Ok(Cave { grid, min_x, sources, floor })
+    }
+
+    fn position(&self, [y,x]: [usize; 2]) -> (i32,i32) {
+        (x as i32 + self.min_x, y as i32)
     }
-}
 
-fn put_sand_floor_and_check_source(cave: &mut HashSet<(i32,i32)>, height_floor: i32) -> bool {
-    let (mut x, mut y) = (500,0);
-    loop {
-        if y == height_floor - 1 {
-            cave.insert((x,y));
-            return (x,y) == (500,0);
+    //lets a grain fall from the source, going down, then down-left, then down-right
+    fn drop_grain(&mut self, source: usize) -> GrainFate {
+        let Some(&start) = self.sources[source].path.last() else {
+            return GrainFate::SourceBlocked;
+        };
+        let (height,width) = self.grid.raw_dim().into_pattern();
+        let mut pos = start;
+        loop {
+            let [y,x] = pos;
+            let mut next = None;
+            for dx in [0,-1,1] {
+                let nx = x as i64 + dx;
+                if y + 1 >= height || nx < 0 || nx >= width as i64 {
+                    return GrainFate::Lost;
+                }
+                if self.grid[[y + 1, nx as usize]] == Cell::Air {
+                    next = Some([y + 1, nx as usize]);
+                    break;
+                }
+            }
+            match next {
+                Some(next) => {
+                    self.sources[source].path.push(next);
+                    pos = next;
+                }
+                None => {
+                    self.grid[pos] = Cell::Sand;
+                    //the cell may also be on the path of another source
+                    for source in self.sources.iter_mut() {
+                        if let Some(k) = source.path.iter().position(|&p| p == pos) {
+                            source.path.truncate(k);
+                        }
+                    }
+                    return GrainFate::Settled;
+                }
+            }
         }
-        else if !cave.contains(&(x,y+1)) {
-            y += 1;
+    }
+
+    //drops grains from every source in turn until each of them is blocked or loses a grain,
+    //and returns the number of grains which came to rest
+    fn pour(&mut self) -> usize {
+        let mut active = vec![true; self.sources.len()];
+        let mut nb_sand = 0;
+        while active.iter().any(|&a| a) {
+            for (source,is_active) in active.iter_mut().enumerate() {
+                if *is_active {
+                    match self.drop_grain(source) {
+                        GrainFate::Settled => nb_sand += 1,
+                        GrainFate::Lost | GrainFate::SourceBlocked => *is_active = false,
+                    }
+                }
+            }
         }
-        else if !cave.contains(&(x-1,y+1)) {
-            x -= 1;
-            y += 1;
+        nb_sand
+    }
+
+    //with a floor, every cell a grain could fall to from a source ends up filled, so the sand at rest is the number
+    //of cells reached from the row above, sweeping the rows from the top
+    fn reachable_cells(&self) -> Result<usize,String> {
+        if let FloorBehaviour::Abyss = self.floor {
+            return Err(String::from("Sand falling in the abyss cannot be counted by reachability"));
         }
-        else if !cave.contains(&(x+1,y+1)) {
-            x += 1;
-            y += 1;
+        let (_height,width) = self.grid.raw_dim().into_pattern();
+        let mut reached = vec![false; width];
+        let mut nb_reached = 0;
+        for (y,row) in self.grid.rows().into_iter().enumerate() {
+            reached = (0..width)
+                .map(|x| row[x] != Cell::Rock && (
+                    reached[x.saturating_sub(1)..(x + 2).min(width)].iter().any(|&r| r)
+                    || self.sources.iter().any(|source| source.pos == self.position([y,x]))))
+                .collect();
+            nb_reached += reached.iter().filter(|&&r| r).count();
         }
-        else {
-            cave.insert((x,y));
-            return (x,y) == (500,0);
+        Ok(nb_reached)
+    }
+
+    //number of grains at rest once the sources are blocked or lose their grains
+    fn settled_sand(&mut self, solver: SandSolver) -> Result<usize,String> {
+        match solver {
+            SandSolver::Simulation => {
+                self.pour();
+                Ok(self.cells(Cell::Sand).len())
+            }
+            SandSolver::Reachability => self.reachable_cells()
         }
     }
+
+    //positions of every cell of the given kind
+    fn cells(&self, kind: Cell) -> Vec<(i32,i32)> {
+        self.grid.indexed_iter()
+            .filter(|(_index,&cell)| cell == kind)
+            .map(|((y,x),_cell)| self.position([y,x]))
+            .collect()
+    }
 }
+
+//draws the cave like in the puzzle statement
+impl Display for Cave {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut drawing = self.grid.map(|cell| match cell {
+            Cell::Air => '.',
+            Cell::Rock => '#',
+            Cell::Sand => 'o',
+        });
+        for source in self.sources.iter() {
+            let index = [source.pos.1 as usize, (source.pos.0 - self.min_x) as usize];
+            if self.grid[index] == Cell::Air {
+                drawing[index] = '+';
+            }
+        }
+        let lines = drawing.rows()
+            .into_iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<String>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
 pub fn day14() {
     let mut file = File::open("./inputs/input_day14.txt").expect("File not found");
     let mut data = String::new();
     file.read_to_string(&mut data)
         .expect("Error while reading file");
 
-    let mut cave: HashSet<(i32,i32)> = HashSet::new();
-    data.lines()
-        .for_each(|line| line.split(" -> ")
-            .map(|pair| {
-                let mut pair_nb = pair.split(',');
-                let width = pair_nb.next().unwrap().parse::<i32>().unwrap();
-                let height = pair_nb.next().unwrap().parse::<i32>().unwrap();
-                (width,height)
-            })
-            .tuple_windows::<(_,_)>()
-            .for_each(|(t1,t2)| {
-                get_line(t1,t2).iter()
-                    .for_each(|(x,y)| { cave.insert((*x, *y)); });
-            }));
-    let height_abyss = cave.iter().map(|t| t.1).max().unwrap();
-    let mut cave2 = cave.clone();
+    let paths = parse_paths(&data).unwrap();
+    let mut cave = Cave::new(&paths, &[(500,0)], FloorBehaviour::Abyss).unwrap();
+    //grains lost in the abyss can only be found by dropping them
+    let nb_sand = cave.settled_sand(SandSolver::Simulation).unwrap();
+    println!("{}", cave);
+
+    let mut cave2 = Cave::new(&paths, &[(500,0)], FloorBehaviour::Floor(2)).unwrap();
+    let nb_sand2 = cave2.settled_sand(SandSolver::Reachability).unwrap();
+    println!("Solution 1 : {:?}",nb_sand);
+    println!("Solution 2 : {:?}",nb_sand2);
+
+    let mut walled = Cave::new(&paths, &[(500,0)], FloorBehaviour::Walls(2)).unwrap();
+    println!("Sand at rest between walls : {}", walled.pour());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut nb_sand = 0;
-    while !put_sand_and_check_abyss(&mut cave,height_abyss) {
-        nb_sand += 1
+    const EXAMPLE: &str = "498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+
+    fn example_cave(sources: &[(i32,i32)], floor: FloorBehaviour) -> Cave {
+        Cave::new(&parse_paths(EXAMPLE).unwrap(), sources, floor).unwrap()
     }
 
-    let mut nb_sand2 = 1;
-    while !put_sand_floor_and_check_source(&mut cave2,height_abyss+2) {
-        nb_sand2 += 1
+    #[test]
+    fn example_abyss() {
+        let mut cave = example_cave(&[(500,0)], FloorBehaviour::Abyss);
+        assert_eq!(cave.pour(), 24);
+        assert_eq!(cave.cells(Cell::Sand).len(), 24);
+        assert!(cave.reachable_cells().is_err());
     }
-    println!("Solution 1 : {:?}",nb_sand);
-    println!("Solution 2 : {:?}",nb_sand2);
-}
\ No newline at end of file
+
+    #[test]
+    fn solvers_agree_on_example_floor() {
+        let mut cave = example_cave(&[(500,0)], FloorBehaviour::Floor(2));
+        assert_eq!(cave.settled_sand(SandSolver::Reachability), Ok(93));
+        assert_eq!(cave.settled_sand(SandSolver::Simulation), Ok(93));
+        assert_eq!(cave.reachable_cells(), Ok(93));
+    }
+
+    #[test]
+    fn floor_below_the_rocks() {
+        let paths = parse_paths(EXAMPLE).unwrap();
+        for floor in [FloorBehaviour::Floor(0), FloorBehaviour::Floor(-20), FloorBehaviour::Walls(0)] {
+            assert!(Cave::new(&paths, &[(500,0)], floor).is_err(), "{:?}", floor);
+        }
+        //the floor right below the lowest rocks
+        let mut cave = example_cave(&[(500,0)], FloorBehaviour::Floor(1));
+        assert_eq!(cave.cells(Cell::Rock).iter().filter(|&&(_x,y)| y == 10).count(), cave.grid.ncols());
+        assert_eq!(cave.reachable_cells(), Ok(cave.pour()));
+    }
+
+    #[test]
+    fn example_walls() {
+        let mut cave = example_cave(&[(500,0)], FloorBehaviour::Walls(2));
+        assert_eq!(cave.reachable_cells(), Ok(47));
+        assert_eq!(cave.pour(), 47);
+        assert_eq!(cave.to_string(), "\
+#......o...#
+#.....ooo..#
+#....ooooo.#
+#...ooooooo#
+#..oo#ooo###
+#.ooo#ooo#.#
+#oo###ooo#.#
+#ooo.oooo#.#
+#oooooooo#.#
+##########.#
+#..........#
+############");
+    }
+
+    #[test]
+    fn example_two_sources() {
+        let sources = [(500,0),(497,2)];
+        let cave = example_cave(&sources, FloorBehaviour::Floor(2));
+        assert_eq!(cave.reachable_cells(), Ok(102));
+        let mut cave = example_cave(&sources, FloorBehaviour::Floor(2));
+        assert_eq!(cave.pour(), 102);
+        //the first grain of the second source rests on the left rocks
+        let mut cave = example_cave(&sources, FloorBehaviour::Abyss);
+        assert!(matches!(cave.drop_grain(1), GrainFate::Settled));
+        assert_eq!(cave.cells(Cell::Sand), vec![(497,5)]);
+        //the sources fill the same pile the first one would fill alone
+        let mut cave = example_cave(&sources, FloorBehaviour::Abyss);
+        assert_eq!(cave.pour(), 24);
+        assert!(cave.to_string().starts_with("......+...\n..........\n...+..o..."));
+    }
+}
@@ -1,11 +1,10 @@
 use std::collections::HashSet;
 use std::fs::File;
 use std::io::Read;
+use std::str::FromStr;
 use interval::interval_set::ToIntervalSet;
 use interval::IntervalSet;
 use gcollections::ops::*;
-use geo::{BooleanOps, CoordsIter};
-use geo_types::{coord, LineString, MultiPolygon, Polygon, Rect};
 
 fn distance(p1: (i32,i32), p2: (i32,i32)) -> i32 {
     (p1.0 - p2.0).abs() + (p1.1 - p2.1).abs()
@@ -24,16 +23,190 @@ struct Beacon {
     pos_y: i32,
 }
 
+//row of part 1 and bound of the square [0,max_coord]² searched in part 2, they are not part of the input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchParameters {
+    row: i32,
+    max_coord: i32,
+}
+
+const EXAMPLE_PARAMETERS: SearchParameters = SearchParameters { row: 10, max_coord: 20 };
+const REAL_PARAMETERS: SearchParameters = SearchParameters { row: 2000000, max_coord: 4000000 };
+
+//either a preset, "example" or "real", or the row and the bound separated by a comma
+impl FromStr for SearchParameters {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "example" => Ok(EXAMPLE_PARAMETERS),
+            "real" => Ok(REAL_PARAMETERS),
+            values => {
+                let (row,max_coord) = values.split_once(',')
+                    .ok_or(format!("Invalid search parameters {}, expected example, real or row,max", values))?;
+                let row = row.trim().parse::<i32>().map_err(|e| e.to_string())?;
+                let max_coord = max_coord.trim().parse::<i32>().map_err(|e| e.to_string())?;
+                if max_coord < 0 {
+                    return Err(format!("The search bound {} is negative", max_coord));
+                }
+                Ok(SearchParameters { row, max_coord })
+            }
+        }
+    }
+}
+
 impl Sensor {
     fn get_no_beacon_row(&self, y: i32) -> IntervalSet<i32> {
         let displacement = self.range_no_beacon - (self.pos_y - y).abs();
-        if displacement > 0 {
+        if displacement >= 0 {
             vec![(self.pos_x - displacement,self.pos_x + displacement)].to_interval_set()
         }
         else {
             IntervalSet::empty()
         }
     }
+
+    #[cfg(test)]
+    fn get_no_beacon_column(&self, x: i32) -> IntervalSet<i32> {
+        let displacement = self.range_no_beacon - (self.pos_x - x).abs();
+        if displacement >= 0 {
+            vec![(self.pos_y - displacement,self.pos_y + displacement)].to_interval_set()
+        }
+        else {
+            IntervalSet::empty()
+        }
+    }
+
+    fn covers(&self, point: (i32,i32)) -> bool {
+        distance((self.pos_x,self.pos_y), point) <= self.range_no_beacon
+    }
+}
+
+#[cfg(test)]
+fn covering_sensors(sensors: &[Sensor], point: (i32,i32)) -> Vec<&Sensor> {
+    sensors.iter().filter(|sensor| sensor.covers(point)).collect()
+}
+
+#[cfg(test)]
+fn is_covered(sensors: &[Sensor], point: (i32,i32)) -> bool {
+    sensors.iter().any(|sensor| sensor.covers(point))
+}
+
+fn covered_row(sensors: &[Sensor], y: i32) -> IntervalSet<i32> {
+    sensors.iter()
+        .map(|sensor| sensor.get_no_beacon_row(y))
+        .fold(IntervalSet::empty(), |accum, item| accum.union(&item))
+}
+
+#[cfg(test)]
+fn covered_column(sensors: &[Sensor], x: i32) -> IntervalSet<i32> {
+    sensors.iter()
+        .map(|sensor| sensor.get_no_beacon_column(x))
+        .fold(IntervalSet::empty(), |accum, item| accum.union(&item))
+}
+
+#[cfg(test)]
+//number of covered cells in the rectangle between the two corners, both included
+fn covered_area(sensors: &[Sensor], (x_min,y_min): (i32,i32), (x_max,y_max): (i32,i32)) -> u64 {
+    let columns = vec![(x_min,x_max)].to_interval_set();
+    (y_min..=y_max)
+        .map(|y| covered_row(sensors, y).intersection(&columns).size() as u64)
+        .sum()
+}
+
+//coverage of the square [min,max]² drawn on width x height characters, each one telling whether the center of its
+//block is covered, unless the block holds a sensor (S) or a beacon (B)
+fn render_coverage(sensors: &[Sensor], beacons: &[Beacon], min: i32, max: i32, width: usize, height: usize) -> String {
+    let side = (max - min) as i64 + 1;
+    let (width,height) = (width.min(side as usize), height.min(side as usize));
+    let block = |k: usize, nb_blocks: usize| (min as i64 + k as i64 * side / nb_blocks as i64) as i32;
+    let in_block = |(x,y): (i32,i32), i: usize, j: usize| x >= block(j, width) && x < block(j + 1, width)
+        && y >= block(i, height) && y < block(i + 1, height);
+    (0..height)
+        .map(|i| (0..width)
+            .map(|j| {
+                if sensors.iter().any(|sensor| in_block((sensor.pos_x,sensor.pos_y), i, j)) {
+                    'S'
+                }
+                else if beacons.iter().any(|beacon| in_block((beacon.pos_x,beacon.pos_y), i, j)) {
+                    'B'
+                }
+                else {
+                    let center = ((block(j, width) + block(j + 1, width)) / 2, (block(i, height) + block(i + 1, height)) / 2);
+                    if sensors.iter().any(|sensor| sensor.covers(center)) { '#' } else { '.' }
+                }
+            })
+            .collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+//cells of the square [min,max]² covered by no sensor, found row by row : once a row is covered, the margins by which
+//the sensor intervals overlap each other and the edges tell how many of the next rows are surely covered too,
+//since an interval end moves by at most one per row. Stops after the first limit cells
+fn uncovered_cells(sensors: &[Sensor], min: i32, max: i32, limit: usize) -> Vec<(i32,i32)> {
+    let mut uncovered = Vec::new();
+    let mut intervals: Vec<(i64,i64)> = Vec::with_capacity(sensors.len());
+    let (min,max) = (min as i64, max as i64);
+    let mut y = min;
+    while y <= max && uncovered.len() < limit {
+        intervals.clear();
+        intervals.extend(sensors.iter()
+            .map(|sensor| (sensor, sensor.range_no_beacon as i64 - (sensor.pos_y as i64 - y).abs()))
+            .filter(|(_sensor,displacement)| *displacement >= 0)
+            .map(|(sensor,displacement)| (sensor.pos_x as i64 - displacement, sensor.pos_x as i64 + displacement)));
+        intervals.sort_unstable();
+        //last covered cell of the row, and smallest margin while it is covered
+        let mut reach = min - 1;
+        let mut margin = i64::MAX;
+        for &(start,end) in intervals.iter() {
+            if end <= reach {
+                continue;
+            }
+            if start > reach + 1 {
+                uncovered.extend((reach + 1..start.min(max + 1)).map(|x| (x as i32,y as i32)).take(limit - uncovered.len()));
+                margin = -1;
+            }
+            else if reach < min {
+                margin = margin.min(min - start);
+            }
+            else {
+                margin = margin.min((reach + 1 - start) / 2);
+            }
+            reach = end;
+            if reach >= max {
+                break;
+            }
+        }
+        if reach < max {
+            uncovered.extend((reach + 1..=max).map(|x| (x as i32,y as i32)).take(limit - uncovered.len()));
+            margin = -1;
+        }
+        else {
+            margin = margin.min(reach - max);
+        }
+        y += 1 + margin.max(0);
+    }
+    uncovered
+}
+
+//the only cell of the square [0,max]² where the distress beacon can be
+fn distress_beacon(sensors: &[Sensor], max: i32) -> Result<(i32,i32),String> {
+    match uncovered_cells(sensors, 0, max, 2).as_slice() {
+        [] => Err(String::from("Every cell of the search area is covered by a sensor")),
+        [cell] => Ok(*cell),
+        [first, second, ..] => Err(format!("Several cells are not covered by any sensor, such as {:?} and {:?}", first, second)),
+    }
+}
+
+//positions of the row where no beacon can be, the known beacons of the row excepted
+fn no_beacon_positions(sensors: &[Sensor], beacons: &[Beacon], y: i32) -> u32 {
+    let covered = covered_row(sensors, y);
+    let beacons_on_row = beacons.iter()
+        .filter(|beacon| beacon.pos_y == y && covered.contains(&beacon.pos_x))
+        .map(|beacon| beacon.pos_x)
+        .collect::<HashSet<i32>>();
+    covered.size() - beacons_on_row.len() as u32
 }
 
 fn parse(line: &str) -> (Sensor,Beacon) {
@@ -50,54 +223,92 @@ fn parse(line: &str) -> (Sensor,Beacon) {
     },Beacon { pos_x: nbs[2], pos_y: nbs[3] })
 }
 
-pub fn day15() {
+pub fn day15(parameters: SearchParameters) {
     let mut file = File::open("./inputs/input_day15.txt").expect("File not found");
     let mut data = String::new();
     file.read_to_string(&mut data)
         .expect("Error while reading file");
 
-    let row = 2000000;
-    let max_coord = 4000000;
-    let interval_size = data.lines()
-        .map(|l| parse(l).0.get_no_beacon_row(row))
-        .reduce(|accum, item| accum.union(&item))
-        .unwrap()
-        .size();
-    let sol1 = interval_size - data.lines()
+    let (sensors,beacons): (Vec<Sensor>,Vec<Beacon>) = data.lines()
         .map(parse)
-        .filter(|(_sensor,beacon)| beacon.pos_y == row)
-        .map(|(_sensor,beacon)| beacon.pos_y)
-        .collect::<HashSet<i32>>()
-        .len() as u32;
-
-    println!("Solution 1 : {:?}", sol1);
-
-    let mut possible_beacon_zone = MultiPolygon::new(vec![Rect::new(
-        coord! { x: 0., y: 0. },
-        coord! { x: max_coord as f32, y: max_coord as f32}
-    ).to_polygon()]);
-
-    let fig = data.lines()
-        .map(|l| {
-            let sensor = parse(l).0;
-            let sx = sensor.pos_x as f32;
-            let sy = sensor.pos_y as f32;
-            let sr = sensor.range_no_beacon as f32 + 0.5;
-            let v: Vec<(f32,f32)> = vec![(sx - sr,sy), (sx,sy - sr), (sx + sr,sy), (sx,sy + sr), (sx - sr, sy)];
-            MultiPolygon::new(vec![Polygon::new(
-                LineString::from(v),
-                vec![])])
-        });
-    for mp in fig {
-        possible_beacon_zone = possible_beacon_zone.difference(&mp);
+        .unzip();
+
+    println!("Solution 1 : {:?}", no_beacon_positions(&sensors, &beacons, parameters.row));
+    match distress_beacon(&sensors, parameters.max_coord) {
+        Ok((x,y)) => {
+            println!("Solution 2 : {:?}", x as u64 * 4000000 + y as u64);
+        }
+        Err(e) => println!("Solution 2 : {}", e)
     }
+    println!("{}", render_coverage(&sensors, &beacons, 0, parameters.max_coord, 40, 20));
+}
 
-    let (ix,iy): (Vec<f32>,Vec<f32>) = possible_beacon_zone.0[0]
-        .coords_iter()
-        .map(|g| g.x_y())
-        .unzip();
-    let x_dis = *ix.iter().find(|x| x.fract() < 0.1).unwrap() as u64;
-    let y_dis = *iy.iter().find(|y| y.fract() < 0.1).unwrap() as u64;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "Sensor at x=2, y=18: closest beacon is at x=-2, y=15
+Sensor at x=9, y=16: closest beacon is at x=10, y=16
+Sensor at x=13, y=2: closest beacon is at x=15, y=3
+Sensor at x=12, y=14: closest beacon is at x=10, y=16
+Sensor at x=10, y=20: closest beacon is at x=10, y=16
+Sensor at x=14, y=17: closest beacon is at x=10, y=16
+Sensor at x=8, y=7: closest beacon is at x=2, y=10
+Sensor at x=2, y=0: closest beacon is at x=2, y=10
+Sensor at x=0, y=11: closest beacon is at x=2, y=10
+Sensor at x=20, y=14: closest beacon is at x=25, y=17
+Sensor at x=17, y=20: closest beacon is at x=21, y=22
+Sensor at x=16, y=7: closest beacon is at x=15, y=3
+Sensor at x=14, y=3: closest beacon is at x=15, y=3
+Sensor at x=20, y=1: closest beacon is at x=15, y=3";
+
+    fn example() -> (Vec<Sensor>,Vec<Beacon>) {
+        EXAMPLE.lines().map(parse).unzip()
+    }
+
+    #[test]
+    fn parse_parameters() {
+        assert_eq!(SearchParameters::from_str("example"), Ok(EXAMPLE_PARAMETERS));
+        assert_eq!(SearchParameters::from_str("real\n"), Ok(REAL_PARAMETERS));
+        assert_eq!(SearchParameters::from_str("10, 20"), Ok(SearchParameters { row: 10, max_coord: 20 }));
+        assert!(SearchParameters::from_str("10").is_err());
+        assert!(SearchParameters::from_str("10,-1").is_err());
+    }
+
+    #[test]
+    fn solve_example() {
+        let (sensors,beacons) = example();
+        assert_eq!(no_beacon_positions(&sensors, &beacons, EXAMPLE_PARAMETERS.row), 26);
+        assert_eq!(distress_beacon(&sensors, EXAMPLE_PARAMETERS.max_coord), Ok((14,11)));
+    }
 
-    println!("Solution 2 : {:?}", x_dis * max_coord + y_dis);
-}
\ No newline at end of file
+    #[test]
+    fn coverage_queries() {
+        let (sensors,_beacons) = example();
+        assert!(!is_covered(&sensors, (14,11)));
+        assert!(is_covered(&sensors, (15,11)));
+        let covering = covering_sensors(&sensors, (15,11)).iter().map(|sensor| (sensor.pos_x,sensor.pos_y)).collect::<Vec<(i32,i32)>>();
+        assert_eq!(covering, vec![(20,14),(16,7)]);
+        assert_eq!(covered_row(&sensors, 10), vec![(-2,24)].to_interval_set());
+        assert_eq!(covered_column(&sensors, 14), vec![(0,10),(12,23)].to_interval_set());
+        assert_eq!(covered_column(&sensors, 15), vec![(-1,24)].to_interval_set());
+        assert_eq!(covered_area(&sensors, (9,6), (19,16)), 120);
+        assert_eq!(covered_area(&sensors, (14,11), (14,11)), 0);
+    }
+
+    #[test]
+    fn render_example() {
+        let (sensors,beacons) = example();
+        //one character per cell once the size is capped to the side of the square
+        let map = render_coverage(&sensors, &beacons, 0, 20, 40, 40);
+        let lines = map.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 21);
+        assert_eq!(lines[0], "##S##################");
+        assert_eq!(lines[3], "##############SB#####");
+        assert_eq!(lines[11], "S#############.######");
+        assert_eq!(map.chars().filter(|&c| c == 'S').count(), 14);
+        assert_eq!(map.chars().filter(|&c| c == 'B').count(), 3);
+        assert_eq!(map.chars().filter(|&c| c == '.').count(), 1);
+        assert_eq!(render_coverage(&sensors, &beacons, 0, 20, 7, 3), "S###SBS\nS#S##S#\nS##SSSS");
+    }
+}
@@ -1,63 +1,136 @@
 use std::collections::HashMap;
 use std::fs::File;
-use std::hash::Hash;
 use std::io::Read;
 
-#[derive(Debug, Eq, PartialEq, Hash)]
-struct Time(u32);
+//best_pressures keeps one entry per set of valves with a positive flow, 2^24 of them already take 64MB
+const MAX_OPERABLE: usize = 24;
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 struct ValveId(String);
 
+//set of valves of the compressed graph, the valve of rank r being the bit r
 #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
 struct BitSetOperable(usize);
 
-fn get_rank(valve_id: ValveId, operable_ids: &[ValveId]) -> Option<usize> {
-    operable_ids.iter()
-        .position(|id| *id == valve_id)
-}
 impl BitSetOperable {
-    fn contains(&self, valve_id: ValveId, operable_ids: &[ValveId]) -> bool {
-        get_rank(valve_id,operable_ids).map(|r| (1 << r) & self.0 != 0).unwrap_or(false)
+    fn contains(&self, rank: u8) -> bool {
+        (1 << rank) & self.0 != 0
     }
 
-    fn remove(&self, valve_id: ValveId, operable_ids: &[ValveId]) -> BitSetOperable {
-        BitSetOperable(self.0 - (1 << get_rank(valve_id,operable_ids).unwrap()))
+    fn insert(&self, rank: u8) -> BitSetOperable {
+        BitSetOperable(self.0 | (1 << rank))
     }
 }
+
 #[derive(Debug)]
 struct Valve {
     flow_rate: u32,
     neighbors: Vec<ValveId>,
 }
 
-fn fill_dynamic(array : &mut HashMap<(Time, ValveId, BitSetOperable),u32>, operable_ids: &[ValveId], valves: &HashMap<ValveId,Valve>) {
-    for time in 1..30 {
-        for pos in valves.keys() {
-            for x in 0..2_usize.pow(operable_ids.len() as u32) {
-                let b = BitSetOperable(x);
-                let key = (Time(time),pos.clone(),b);
-                let mut o = *array.get(&key).unwrap_or(&0);
-
-                if b.contains(pos.clone(),operable_ids) {
-                    let key_b = (Time(time-1),pos.clone(),b.remove(pos.clone(),operable_ids));
-                    o = o.max(array.get(&key_b).unwrap_or(&0) + valves[pos].flow_rate * time);
+//the valves with a positive flow, ranked from 0, followed by the start valve, and the minutes needed to walk
+//from any of them to any other
+#[derive(Debug)]
+struct ValveGraph {
+    flow_rates: Vec<u32>,
+    distances: Vec<Vec<u32>>,
+    start: u8,
+}
+
+impl ValveGraph {
+    fn new(valves: &HashMap<ValveId,Valve>, start: &ValveId) -> Result<Self,String> {
+        let ids = valves.keys().collect::<Vec<&ValveId>>();
+        let index = ids.iter()
+            .enumerate()
+            .map(|(i,&id)| (id,i))
+            .collect::<HashMap<&ValveId,usize>>();
+        //Floyd–Warshall on the whole graph
+        let n = ids.len();
+        let mut all_distances = vec![vec![u32::MAX; n]; n];
+        for (i,id) in ids.iter().enumerate() {
+            all_distances[i][i] = 0;
+            for neighbor in valves[id].neighbors.iter() {
+                let j = *index.get(neighbor).ok_or(format!("Unknown valve {}", neighbor.0))?;
+                all_distances[i][j] = 1;
+            }
+        }
+        for k in 0..n {
+            for i in 0..n {
+                for j in 0..n {
+                    let through_k = all_distances[i][k].saturating_add(all_distances[k][j]);
+                    if through_k < all_distances[i][j] {
+                        all_distances[i][j] = through_k;
+                    }
                 }
+            }
+        }
+        let mut kept = ids.iter()
+            .enumerate()
+            .filter(|(_i,id)| valves[*id].flow_rate > 0)
+            .map(|(i,_id)| i)
+            .collect::<Vec<usize>>();
+        if kept.len() > MAX_OPERABLE {
+            return Err(format!("Too many valves with a positive flow : {}, at most {} are supported", kept.len(), MAX_OPERABLE));
+        }
+        kept.push(*index.get(start).ok_or(format!("Unknown start valve {}", start.0))?);
+        Ok(ValveGraph {
+            flow_rates: kept.iter().map(|&i| valves[ids[i]].flow_rate).collect(),
+            distances: kept.iter().map(|&i| kept.iter().map(|&j| all_distances[i][j]).collect()).collect(),
+            start: (kept.len() - 1) as u8,
+        })
+    }
+
+    fn nb_operable(&self) -> usize {
+        self.start as usize
+    }
 
-                for pos_neighbor in valves[pos].neighbors.iter() {
-                    let key_b = (Time(time-1),pos_neighbor.clone(),b);
-                    o = o.max(*array.get(&key_b).unwrap_or(&0))
+    //indexed by set of opened valves, most pressure released in the given time when opening exactly these valves
+    fn best_pressures(&self, time: u32) -> Vec<u32> {
+        let mut best = vec![0; 1 << self.nb_operable()];
+        let mut to_visit = vec![(self.start, time, BitSetOperable(0), 0)];
+        while let Some((pos, time_left, opened, pressure)) = to_visit.pop() {
+            best[opened.0] = best[opened.0].max(pressure);
+            for next in 0..self.start {
+                //walking there, then one minute to open it
+                let time_needed = self.distances[pos as usize][next as usize].saturating_add(1);
+                if !opened.contains(next) && time_needed < time_left {
+                    let remaining = time_left - time_needed;
+                    to_visit.push((next, remaining, opened.insert(next), pressure + self.flow_rates[next as usize] * remaining));
                 }
-                array.insert(key,o);
             }
         }
+        best
     }
 }
+
+//turns the best pressures for exactly a set of valves into the best ones for any subset of it
+fn best_for_subsets(best: &mut [u32], nb_operable: usize) {
+    for rank in 0..nb_operable {
+        for set in 0..best.len() {
+            if BitSetOperable(set).contains(rank as u8) {
+                best[set] = best[set].max(best[set ^ (1 << rank)]);
+            }
+        }
+    }
+}
+
+//the elephant and I open disjoint sets of valves
+fn best_pressure_with_elephant(graph: &ValveGraph, time: u32) -> u32 {
+    let nb_op = graph.nb_operable();
+    let mut best = graph.best_pressures(time);
+    best_for_subsets(&mut best, nb_op);
+    let all = (1 << nb_op) - 1;
+    (0..best.len())
+        .map(|set| best[set] + best[all ^ set])
+        .max()
+        .unwrap()
+}
+
 fn parse(line: &str) -> (Valve,ValveId) {
     let mut it = line.split(';');
     let mut first_part = it.next().unwrap().split_whitespace();
     let valve_id = first_part.nth(1).unwrap();
-    let flow_rate = first_part.last().unwrap().split('=').last().unwrap().parse::<u32>().unwrap();
+    let flow_rate = first_part.last().unwrap().split('=').next_back().unwrap().parse::<u32>().unwrap();
     let neighbors = it.next().unwrap().split_whitespace().skip(4)
         .map(|s| ValveId(s.chars().filter(|&c| c!=',').collect::<String>())).collect();
     (Valve { flow_rate, neighbors }, ValveId(valve_id.to_owned()))
@@ -75,24 +148,54 @@ pub fn day16() {
             let (valve,valve_id) = parse(l);
             valves.insert(valve_id,valve);
         });
-    let operable_valves = valves.iter()
-        .filter(|(_id,valve)| valve.flow_rate != 0)
-        .map(|(id,_valve)| id.clone())
-        .collect::<Vec<ValveId>>();
-
-    let nb_op = operable_valves.len();
-    let bitset_all = BitSetOperable((1 << nb_op) - 1);
-    let mut array = HashMap::new();
-    fill_dynamic(&mut array,&operable_valves,&valves);
-
-    println!("Solution 1 : {:?}",array.get(&(Time(29), ValveId(String::from("AA")), bitset_all)).unwrap());
-
-    let mut best = 0;
-    for x in 0..(1 << nb_op)/2 {
-        let b1 = BitSetOperable(x);
-        let b2 = BitSetOperable((1 << nb_op) - 1 - x);
-        best = best.max(array[&(Time(25), ValveId(String::from("AA")), b1)] + array[&(Time(25), ValveId(String::from("AA")), b2)])
+    let graph = ValveGraph::new(&valves, &ValveId(String::from("AA"))).unwrap();
+
+    println!("Solution 1 : {:?}",graph.best_pressures(30).iter().max().unwrap());
+
+    println!("Solution 2 : {:?}",best_pressure_with_elephant(&graph, 26));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II";
+
+    fn valves(data: &str) -> HashMap<ValveId,Valve> {
+        data.lines()
+            .map(|l| {
+                let (valve,valve_id) = parse(l);
+                (valve_id,valve)
+            })
+            .collect()
     }
 
-    println!("Solution 2 : {:?}",best);
-}
\ No newline at end of file
+    #[test]
+    fn solve_example() {
+        let graph = ValveGraph::new(&valves(EXAMPLE), &ValveId(String::from("AA"))).unwrap();
+        assert_eq!(graph.nb_operable(), 6);
+        assert_eq!(graph.best_pressures(30).iter().max(), Some(&1651));
+        assert_eq!(best_pressure_with_elephant(&graph, 26), 1707);
+    }
+
+    #[test]
+    fn too_many_operable_valves() {
+        //a corridor of valves which all have a positive flow
+        let corridor = |n: usize| (0..=n)
+            .map(|i| format!("Valve V{} has flow rate={}; tunnels lead to valves V{}, V{}", i, i.min(1), i.saturating_sub(1), (i + 1).min(n)))
+            .collect::<Vec<String>>()
+            .join("\n");
+        assert!(ValveGraph::new(&valves(&corridor(MAX_OPERABLE)), &ValveId(String::from("V0"))).is_ok());
+        assert_eq!(ValveGraph::new(&valves(&corridor(MAX_OPERABLE + 1)), &ValveId(String::from("V0"))).unwrap_err(),
+            format!("Too many valves with a positive flow : {}, at most {} are supported", MAX_OPERABLE + 1, MAX_OPERABLE));
+    }
+}
@@ -91,7 +91,7 @@ pub fn day17() {
         .cycle();
 
     let mut rocks: HashSet<(i32,i32)> = HashSet::new();
-    let rock_types = vec![Rock::make_rock1(),Rock::make_rock2(),Rock::make_rock3(),Rock::make_rock4(),Rock::make_rock5()];
+    let rock_types = [Rock::make_rock1(),Rock::make_rock2(),Rock::make_rock3(),Rock::make_rock4(),Rock::make_rock5()];
     let rock_iterator = rock_types
         .iter()
         .cycle()
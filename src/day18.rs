@@ -9,7 +9,7 @@ struct Coordinates(i32,i32,i32);
 
 impl Coordinates {
     fn neighbors(&self) -> Vec<Coordinates> {
-        vec![(1,0,0),(0,1,0),(0,0,1),(-1,0,0),(0,-1,0),(0,0,-1)].iter()
+        [(1,0,0),(0,1,0),(0,0,1),(-1,0,0),(0,-1,0),(0,0,-1)].iter()
             .map(|(x,y,z)| Coordinates(self.0 + x,self.1 + y, self.2 + z))
             .collect()
     }
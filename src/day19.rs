@@ -4,6 +4,7 @@ use std::fs::File;
 use std::io::Read;
 use crate::day19::Resource::{Geode, Obsidian};
 
+#[allow(dead_code)]
 #[derive(Debug)]
 enum Resource {
     Ore,
@@ -314,11 +314,10 @@ impl Instruction {
 }
 
 fn assign_vertices_to_net(net: &HashSet<(i32,i32)>) -> HashMap<(i32,i32),[[char;2];2]>{
-    let dirs = vec![CubeRoll::Up,CubeRoll::Down,CubeRoll::Left,CubeRoll::Right];
+    let dirs = [CubeRoll::Up,CubeRoll::Down,CubeRoll::Left,CubeRoll::Right];
     let mut visited_faces = HashMap::new();
     let mut to_visit = vec![(*net.iter().next().unwrap(),Cube::new())];
-    while !to_visit.is_empty() {
-        let ((x,y),cube) = to_visit.pop().unwrap();
+    while let Some(((x,y),cube)) = to_visit.pop() {
         visited_faces.insert((x,y),cube.vertices[0]);
         for roll in dirs.iter() {
             let (dx,dy) = roll.get_disp();
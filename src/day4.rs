@@ -1,43 +1,117 @@
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
+use interval::interval_set::ToIntervalSet;
+use interval::{Interval, IntervalSet};
+use interval::ops::Range;
+use gcollections::ops::*;
+use itertools::Itertools;
 
-struct ElvesPair {
-    start_first: u32,
-    end_first: u32,
-    start_second: u32,
-    end_second: u32
+struct ElvesGroup {
+    sections: Vec<Interval<u32>>
 }
 
-impl ElvesPair {
+struct Assignments {
+    groups: Vec<ElvesGroup>
+}
+
+impl ElvesGroup {
     fn contains_other(&self) -> bool {
-        (self.start_first >= self.start_second && self.end_first <= self.end_second) ||
-            (self.start_first <= self.start_second && self.end_first >= self.end_second)
+        self.sections.iter()
+            .tuple_combinations()
+            .any(|(first,second)| first.is_subset(second) || second.is_subset(first))
     }
 
     fn overlap(&self) -> bool {
-        self.end_first >= self.start_second && self.start_first <= self.end_second
+        self.sections.iter()
+            .tuple_combinations()
+            .any(|(first,second)| first.overlap(second))
+    }
+
+    fn coverage(&self) -> IntervalSet<u32> {
+        self.sections.iter()
+            .map(|section| (section.lower(),section.upper()))
+            .sorted()
+            .collect::<Vec<(u32,u32)>>()
+            .to_interval_set()
+    }
+}
+
+impl Assignments {
+    fn union(&self) -> IntervalSet<u32> {
+        self.groups.iter()
+            .map(|group| group.coverage())
+            .fold(IntervalSet::empty(), |accum, item| accum.union(&item))
+    }
+
+    fn unassigned(&self) -> IntervalSet<u32> {
+        let covered = self.union();
+        if covered.is_empty() {
+            return covered;
+        }
+        (covered.lower(),covered.upper())
+            .to_interval_set()
+            .difference(&covered)
+    }
+
+    //returns the section covered by the most elves and how many of them cover it,
+    //the smallest section wins in case of ties
+    fn most_covered(&self) -> Option<(u32,usize)> {
+        let events = self.groups.iter()
+            .flat_map(|group| group.sections.iter())
+            .flat_map(|section| [(section.lower() as i64,1),(section.upper() as i64 + 1,-1)])
+            .sorted();
+        let mut covering = 0;
+        let mut best: Option<(u32,usize)> = None;
+        for (section,delta) in events {
+            covering += delta;
+            if delta > 0 && best.is_none_or(|(_s,count)| covering as usize > count) {
+                best = Some((section as u32, covering as usize));
+            }
+        }
+        best
+    }
+
+    //returns (line index, elf index in the line) of every elf assigned to the section
+    fn elves_covering(&self, section: u32) -> Vec<(usize,usize)> {
+        self.groups.iter()
+            .enumerate()
+            .flat_map(|(i,group)| group.sections.iter()
+                .enumerate()
+                .filter(move |(_j,elf_sections)| elf_sections.contains(&section))
+                .map(move |(j,_elf_sections)| (i,j)))
+            .collect()
     }
 }
 
-impl FromStr for ElvesPair {
+impl FromStr for ElvesGroup {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut it = s.split(',')
+        let sections = s.split(',')
             .map(|sections| {
-                sections.split('-')
-                    .map(|section| u32::from_str(section).unwrap())
-                    .collect::<Vec<u32>>()
-            });
-        let sec1 = it.next().unwrap();
-        let sec2 = it.next().unwrap();
-        Ok(ElvesPair {
-            start_first: sec1[0],
-            end_first: sec1[1],
-            start_second: sec2[0],
-            end_second: sec2[1],
-        })
+                let (start,end) = sections.split_once('-')
+                    .ok_or(format!("Invalid sections {}", sections))?;
+                let start = u32::from_str(start).map_err(|e| e.to_string())?;
+                let end = u32::from_str(end).map_err(|e| e.to_string())?;
+                if start > end {
+                    return Err(format!("Invalid sections {}", sections));
+                }
+                Ok(Interval::new(start,end))
+            })
+            .collect::<Result<Vec<Interval<u32>>,String>>()?;
+        Ok(ElvesGroup { sections })
+    }
+}
+
+impl FromStr for Assignments {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let groups = s.lines()
+            .map(ElvesGroup::from_str)
+            .collect::<Result<Vec<ElvesGroup>,String>>()?;
+        Ok(Assignments { groups })
     }
 }
 
@@ -46,16 +120,62 @@ pub fn day4() {
     let mut data = String::new();
     file.read_to_string(&mut data)
         .expect("Error while reading file");
-    let nb_contains_other = data.split('\n')
-        .map(|str| ElvesPair::from_str(str).unwrap())
-        .filter(|pair| pair.contains_other())
+    let assignments = Assignments::from_str(&data).unwrap();
+    let nb_contains_other = assignments.groups.iter()
+        .filter(|group| group.contains_other())
         .count();
 
-    let nb_overlap = data.split('\n')
-        .map(|str| ElvesPair::from_str(str).unwrap())
-        .filter(|pair| pair.overlap())
+    let nb_overlap = assignments.groups.iter()
+        .filter(|group| group.overlap())
         .count();
 
     println!("Solution 1 : {:?}", nb_contains_other);
     println!("Solution 2 : {:?}", nb_overlap);
-}
\ No newline at end of file
+
+    println!("Sections assigned : {}", assignments.union());
+    println!("Sections assigned to nobody : {}", assignments.unassigned());
+    if let Some((section,count)) = assignments.most_covered() {
+        println!("Most covered section : {} ({} elves : {:?})", section, count, assignments.elves_covering(section));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "2-4,6-8
+2-3,4-5
+5-7,7-9
+2-8,3-7
+6-6,4-6
+2-6,4-8";
+
+    #[test]
+    fn solve_example() {
+        let assignments = Assignments::from_str(EXAMPLE).unwrap();
+        assert_eq!(assignments.groups.iter().filter(|group| group.contains_other()).count(), 2);
+        assert_eq!(assignments.groups.iter().filter(|group| group.overlap()).count(), 4);
+    }
+
+    #[test]
+    fn coverage_queries_on_example() {
+        let assignments = Assignments::from_str(EXAMPLE).unwrap();
+        assert_eq!(assignments.union(), vec![(2,9)].to_interval_set());
+        assert!(assignments.unassigned().is_empty());
+        assert_eq!(assignments.most_covered(), Some((6,8)));
+        assert_eq!(assignments.elves_covering(6), vec![(0,1),(2,0),(3,0),(3,1),(4,0),(4,1),(5,0),(5,1)]);
+        assert_eq!(assignments.elves_covering(1), vec![]);
+    }
+
+    #[test]
+    fn coverage_queries_with_gaps() {
+        let assignments = Assignments::from_str(&format!("{}\n12-13,15-15", EXAMPLE)).unwrap();
+        assert_eq!(assignments.unassigned(), vec![(10,11),(14,14)].to_interval_set());
+        //ties go to the smallest section
+        let assignments = Assignments::from_str("3-4,1-2").unwrap();
+        assert_eq!(assignments.most_covered(), Some((1,1)));
+        let assignments = Assignments::from_str("").unwrap();
+        assert!(assignments.unassigned().is_empty());
+        assert_eq!(assignments.most_covered(), None);
+    }
+}
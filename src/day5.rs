@@ -1,56 +1,177 @@
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
 use itertools::Itertools;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Cargo {
     crates: Vec<Vec<char>>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Move {
     origin: usize,
     destination: usize,
     number: usize
 }
 
-impl Cargo {
-    fn make_move(&mut self, crates_move: &Move) {
-        for _ in 1..=crates_move.number {
-            let x = self.crates[crates_move.origin-1].pop().unwrap();
-            self.crates[crates_move.destination-1].push(x)
+trait Crane {
+    //checks that the crane is able to perform the move, before any crate is lifted
+    fn check_move(&self, _crates_move: &Move) -> Result<(),String> {
+        Ok(())
+    }
+
+    //receives the lifted crates from bottom to top, returns them in the order they are put down
+    fn put_down(&self, lifted: Vec<char>) -> Vec<char>;
+}
+
+struct CrateMover9000;
+
+struct CrateMover9001;
+
+struct CrateMoverLimited {
+    max_lift: usize
+}
+
+impl Crane for CrateMover9000 {
+    fn put_down(&self, lifted: Vec<char>) -> Vec<char> {
+        lifted.into_iter().rev().collect()
+    }
+}
+
+impl Crane for CrateMover9001 {
+    fn put_down(&self, lifted: Vec<char>) -> Vec<char> {
+        lifted
+    }
+}
+
+impl Crane for CrateMoverLimited {
+    fn check_move(&self, crates_move: &Move) -> Result<(),String> {
+        if crates_move.number > self.max_lift {
+            Err(format!("cannot lift {} crates at once, the maximum is {}", crates_move.number, self.max_lift))
+        }
+        else {
+            Ok(())
         }
     }
 
-    fn make_move_9001(&mut self, crates_move: &Move){
-        let mut buffer = Vec::new();
-        for _ in 1..=crates_move.number {
-            let x = self.crates[crates_move.origin-1].pop().unwrap();
-            buffer.push(x);
+    fn put_down(&self, lifted: Vec<char>) -> Vec<char> {
+        lifted
+    }
+}
+
+impl Cargo {
+    //applies the move and returns the lifted crates from bottom to top, so that it can be undone
+    fn make_move(&mut self, crane: &impl Crane, crates_move: &Move, move_nb: usize) -> Result<Vec<char>,String> {
+        for stack in [crates_move.origin, crates_move.destination] {
+            if stack == 0 || stack > self.crates.len() {
+                return Err(format!("Move {} : stack {} does not exist", move_nb, stack));
+            }
         }
-        for _ in 1..=crates_move.number {
-            let x = buffer.pop().unwrap();
-            self.crates[crates_move.destination-1].push(x);
+        crane.check_move(crates_move)
+            .map_err(|e| format!("Move {} : stack {} : {}", move_nb, crates_move.origin, e))?;
+        let origin = &mut self.crates[crates_move.origin-1];
+        if origin.len() < crates_move.number {
+            return Err(format!("Move {} : stack {} has only {} crates, cannot move {}",
+                               move_nb, crates_move.origin, origin.len(), crates_move.number));
         }
+        let lifted = origin.split_off(origin.len() - crates_move.number);
+        self.crates[crates_move.destination-1].extend(crane.put_down(lifted.clone()));
+        Ok(lifted)
+    }
 
+    #[cfg(test)]
+    fn undo_move(&mut self, crates_move: &Move, lifted: Vec<char>) {
+        let destination = &mut self.crates[crates_move.destination-1];
+        destination.truncate(destination.len() - crates_move.number);
+        self.crates[crates_move.origin-1].extend(lifted);
     }
+
+    //empty stacks appear as an underscore in the message
     fn get_message(&self) -> String {
         self.crates.iter()
-            .map(|v| v.last().unwrap())
+            .map(|v| v.last().copied().unwrap_or('_'))
             .join("")
     }
 }
 
+struct CraneSimulation<C: Crane> {
+    crane: C,
+    initial: Cargo,
+    cargo: Cargo,
+    moves: Vec<Move>,
+    history: Vec<Vec<char>>,
+}
+
+impl<C: Crane> CraneSimulation<C> {
+    fn new(crane: C, cargo: Cargo, moves: Vec<Move>) -> Self {
+        CraneSimulation { crane, initial: cargo.clone(), cargo, moves, history: Vec::new() }
+    }
+
+    //applies the next move, returns false when every move has already been applied
+    fn step(&mut self) -> Result<bool,String> {
+        let move_nb = self.history.len();
+        match self.moves.get(move_nb) {
+            None => Ok(false),
+            Some(crates_move) => {
+                let lifted = self.cargo.make_move(&self.crane, crates_move, move_nb + 1)?;
+                self.history.push(lifted);
+                Ok(true)
+            }
+        }
+    }
+
+    //reverts the last applied move, returns false when no move has been applied
+    #[cfg(test)]
+    fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            None => false,
+            Some(lifted) => {
+                self.cargo.undo_move(&self.moves[self.history.len()], lifted);
+                true
+            }
+        }
+    }
+
+    //restarts from the initial cargo and applies every move
+    fn replay(&mut self) -> Result<&Cargo,String> {
+        self.cargo = self.initial.clone();
+        self.history.clear();
+        while self.step()? {}
+        Ok(&self.cargo)
+    }
+}
+
 impl FromStr for Move {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut it = s.split(' ').filter_map(|s| s.parse::<usize>().ok());
-        let number = it.next().unwrap();
-        let origin = it.next().unwrap();
-        let destination = it.next().unwrap();
-        Ok(Move {origin, destination, number})
+        match (it.next(), it.next(), it.next()) {
+            (Some(number), Some(origin), Some(destination)) => Ok(Move {origin, destination, number}),
+            _ => Err(format!("Invalid move {}", s))
+        }
+    }
+}
+
+//draws the cargo with the same layout as the puzzle input, so that parsing it gives back the same cargo
+impl Display for Cargo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let height = self.crates.iter().map(|v| v.len()).max().unwrap_or(0);
+        for level in (0..height).rev() {
+            let line = self.crates.iter()
+                .map(|v| match v.get(level) {
+                    Some(c) => format!("[{}]", c),
+                    None => String::from("   ")
+                })
+                .join(" ");
+            writeln!(f, "{}", line)?;
+        }
+        let labels = (1..=self.crates.len())
+            .map(|i| format!("{:^3}", i))
+            .join(" ");
+        write!(f, "{}", labels)
     }
 }
 
@@ -61,11 +182,11 @@ impl FromStr for Cargo {
         let mut it = s.split('\n');
         let nb_stacks = it
             .next_back()
-            .unwrap()
+            .ok_or("Empty drawing")?
             .split_whitespace()
             .filter_map(|s| s.parse::<usize>().ok())
             .max()
-            .unwrap();
+            .ok_or("Missing stack labels")?;
         let mut crates: Vec<Vec<char>> = vec![Vec::new(); nb_stacks];
         it.rev().for_each(|s| {
             let mut it_ligne = s.chars();
@@ -87,15 +208,125 @@ pub fn day5() {
     file.read_to_string(&mut data)
         .expect("Error while reading file");
     let mut data_splited = data.split("\n\n");
-    let mut cargo9000 = Cargo::from_str(data_splited.next().unwrap()).unwrap();
-    let mut cargo9001 = cargo9000.clone();
-    data_splited.next().unwrap()
-        .split('\n')
+    let drawing = data_splited.next().unwrap();
+    let cargo = Cargo::from_str(drawing).unwrap();
+    let moves = data_splited.next().unwrap()
+        .lines()
         .map(|s| Move::from_str(s).unwrap())
-        .for_each(|m| {
-            cargo9000.make_move(&m);
-            cargo9001.make_move_9001(&m)
-        });
-    println!("Solution 1 : {:?}", cargo9000.get_message());
-    println!("Solution 2 : {:?}", cargo9001.get_message());
-}
\ No newline at end of file
+        .collect::<Vec<Move>>();
+    let mut simulation9000 = CraneSimulation::new(CrateMover9000, cargo.clone(), moves.clone());
+    let mut simulation9001 = CraneSimulation::new(CrateMover9001, cargo.clone(), moves.clone());
+    let mut simulation_limited = CraneSimulation::new(CrateMoverLimited { max_lift: 3 }, cargo, moves);
+    match simulation9000.replay() {
+        Ok(cargo9000) => println!("Solution 1 : {:?}", cargo9000.get_message()),
+        Err(e) => println!("Solution 1 : {}", e)
+    }
+    match simulation9001.replay() {
+        Ok(cargo9001) => {
+            println!("{}", cargo9001);
+            println!("Solution 2 : {:?}", cargo9001.get_message())
+        },
+        Err(e) => println!("Solution 2 : {}", e)
+    }
+    match simulation_limited.replay() {
+        Ok(cargo_limited) => println!("With a crane lifting at most 3 crates : {:?}", cargo_limited.get_message()),
+        Err(e) => println!("With a crane lifting at most 3 crates : {}", e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_DRAWING: &str = "    [D]    
+[N] [C]    
+[Z] [M] [P]
+ 1   2   3 ";
+
+    const EXAMPLE_MOVES: &str = "move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2";
+
+    fn example_moves() -> Vec<Move> {
+        EXAMPLE_MOVES.lines().map(|s| Move::from_str(s).unwrap()).collect()
+    }
+
+    #[test]
+    fn replay_example() {
+        let cargo = Cargo::from_str(EXAMPLE_DRAWING).unwrap();
+        let mut simulation9000 = CraneSimulation::new(CrateMover9000, cargo.clone(), example_moves());
+        assert_eq!(simulation9000.replay().unwrap().get_message(), "CMZ");
+        let mut simulation9001 = CraneSimulation::new(CrateMover9001, cargo, example_moves());
+        assert_eq!(simulation9001.replay().unwrap().get_message(), "MCD");
+    }
+
+    fn assert_round_trip(cargo: Cargo) {
+        assert_eq!(Cargo::from_str(&cargo.to_string()), Ok(cargo));
+    }
+
+    #[test]
+    fn draw_example() {
+        let cargo = Cargo::from_str(EXAMPLE_DRAWING).unwrap();
+        assert_eq!(cargo.to_string(), EXAMPLE_DRAWING);
+    }
+
+    #[test]
+    fn round_trip_with_empty_stacks() {
+        assert_round_trip(Cargo { crates: vec![vec![], vec!['A'], vec![]] });
+        assert_round_trip(Cargo { crates: vec![vec![], vec![], vec![]] });
+        assert_eq!(Cargo { crates: vec![vec![], vec!['A'], vec![]] }.get_message(), "_A_");
+    }
+
+    #[test]
+    fn round_trip_with_varying_heights() {
+        assert_round_trip(Cargo { crates: vec![vec!['A', 'B', 'C', 'D'], vec![], vec!['E'], vec!['F', 'G']] });
+    }
+
+    #[test]
+    fn draw_ten_stacks_and_more() {
+        let crates = (0..12).map(|i| vec![(b'A' + i as u8) as char; i % 3]).collect::<Vec<Vec<char>>>();
+        let cargo = Cargo { crates };
+        let drawing = cargo.to_string();
+        assert!(drawing.lines().all(|line| line.len() == 12 * 4 - 1));
+        assert!(drawing.ends_with(" 9  10  11  12 "));
+        assert_round_trip(cargo);
+    }
+
+    #[test]
+    fn undo_every_step() {
+        let cargo = Cargo::from_str(EXAMPLE_DRAWING).unwrap();
+        let mut simulation = CraneSimulation::new(CrateMover9001, cargo.clone(), example_moves());
+        let mut states = vec![cargo.clone()];
+        while simulation.step().unwrap() {
+            states.push(simulation.cargo.clone());
+        }
+        states.pop();
+        while simulation.undo() {
+            assert_eq!(simulation.cargo, states.pop().unwrap());
+        }
+        assert_eq!(simulation.cargo, cargo);
+        assert!(!simulation.undo());
+    }
+
+    #[test]
+    fn undo_move_onto_the_same_stack() {
+        let cargo = Cargo::from_str(EXAMPLE_DRAWING).unwrap();
+        let moves = vec![Move { origin: 2, destination: 2, number: 3 }];
+        let mut simulation = CraneSimulation::new(CrateMover9000, cargo.clone(), moves);
+        assert!(simulation.step().unwrap());
+        assert_eq!(simulation.cargo.crates[1], vec!['D', 'C', 'M']);
+        assert!(simulation.undo());
+        assert_eq!(simulation.cargo, cargo);
+    }
+
+    #[test]
+    fn limited_crane_error() {
+        let cargo = Cargo::from_str(EXAMPLE_DRAWING).unwrap();
+        let mut simulation = CraneSimulation::new(CrateMoverLimited { max_lift: 2 }, cargo, example_moves());
+        assert_eq!(simulation.replay(), Err(String::from("Move 2 : stack 1 : cannot lift 3 crates at once, the maximum is 2")));
+        //the failed move is not applied
+        assert_eq!(simulation.history.len(), 1);
+        assert_eq!(simulation.cargo.get_message(), "DCP");
+    }
+}
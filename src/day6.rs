@@ -1,40 +1,101 @@
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
+use std::hash::Hash;
 use std::io::Read;
 
+//keeps a count table of the last `size` symbols, so that each new symbol is handled in constant time
+struct MarkerDetector<T> {
+    size: usize,
+    window: VecDeque<T>,
+    counts: HashMap<T,usize>,
+    nb_read: usize,
+}
+
+impl<T: Hash + Eq + Copy> MarkerDetector<T> {
+    fn new(size: usize) -> Self {
+        MarkerDetector { size, window: VecDeque::with_capacity(size + 1), counts: HashMap::new(), nb_read: 0 }
+    }
+
+    //reads a new symbol, returns the number of symbols read if the last `size` ones are all different
+    fn push(&mut self, symbol: T) -> Option<usize> {
+        self.nb_read += 1;
+        self.window.push_back(symbol);
+        *self.counts.entry(symbol).or_insert(0) += 1;
+        if self.window.len() > self.size {
+            let old = self.window.pop_front().unwrap();
+            let count = self.counts.get_mut(&old).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&old);
+            }
+        }
+        if self.window.len() == self.size && self.counts.len() == self.size {
+            Some(self.nb_read)
+        }
+        else {
+            None
+        }
+    }
+}
+
+fn first_marker<T: Hash + Eq + Copy>(stream: impl IntoIterator<Item = T>, size: usize) -> Option<usize> {
+    all_markers(stream, size).next()
+}
+
+fn all_markers<T: Hash + Eq + Copy>(stream: impl IntoIterator<Item = T>, size: usize) -> impl Iterator<Item = usize> {
+    let mut detector = MarkerDetector::new(size);
+    stream.into_iter().filter_map(move |symbol| detector.push(symbol))
+}
+
 pub fn day6() {
     let mut file = File::open("./inputs/input_day6.txt").expect("File not found");
     let mut data = String::new();
     file.read_to_string(&mut data)
         .expect("Error while reading file");
-    let binding = data.chars().collect::<Vec<char>>();
-    let index = binding
-        .windows(4)
-        .enumerate()
-        .find(|(_i,v)| {
-            for i in 0..4 {
-                for j in 0..i {
-                    if v[i] == v[j]{
-                        return false;
-                    }
-                }
-            }
-            true
-        })
-        .unwrap();
-    let index2 = binding
-        .windows(14)
-        .enumerate()
-        .find(|(_i,v)| {
-            for i in 0..14 {
-                for j in 0..i {
-                    if v[i] == v[j]{
-                        return false;
-                    }
-                }
-            }
-            true
-        })
-        .unwrap();
-    println!("Solution 1 : {:?}", index.0 + 4);
-    println!("Solution 2 : {:?}", index2.0 + 14);
-}
\ No newline at end of file
+    let signal = data.trim();
+    match first_marker(signal.chars(), 4) {
+        Some(index) => println!("Solution 1 : {:?}", index),
+        None => println!("Solution 1 : no start-of-packet marker")
+    }
+    match first_marker(signal.chars(), 14) {
+        Some(index) => println!("Solution 2 : {:?}", index),
+        None => println!("Solution 2 : no start-of-message marker")
+    }
+    println!("Number of start-of-packet positions : {}", all_markers(signal.chars(), 4).count());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLES: [(&str,usize,usize); 5] = [
+        ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+        ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+        ("nppdvjthqldpwncqszvftbrmjlhg", 6, 23),
+        ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsqvjwcj", 10, 29),
+        ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+    ];
+
+    #[test]
+    fn first_marker_on_examples() {
+        for (stream,packet,message) in EXAMPLES {
+            assert_eq!(first_marker(stream.chars(), 4), Some(packet), "{}", stream);
+            assert_eq!(first_marker(stream.chars(), 14), Some(message), "{}", stream);
+        }
+    }
+
+    #[test]
+    fn all_markers_of_any_size() {
+        assert_eq!(all_markers("abab".chars(), 2).collect::<Vec<usize>>(), vec![2, 3, 4]);
+        assert_eq!(all_markers("aab".chars(), 1).collect::<Vec<usize>>(), vec![1, 2, 3]);
+        assert_eq!(all_markers([1,2,1,3,4,4].iter(), 3).collect::<Vec<usize>>(), vec![4, 5]);
+    }
+
+    #[test]
+    fn no_marker() {
+        assert_eq!(first_marker("aaaaaaa".chars(), 2), None);
+        assert_eq!(first_marker("abcabcabc".chars(), 4), None);
+        assert_eq!(first_marker("abc".chars(), 4), None);
+        assert_eq!(all_markers("".chars(), 4).count(), 0);
+    }
+}
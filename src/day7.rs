@@ -1,11 +1,13 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
-use ego_tree::{NodeId, Tree};
+use ego_tree::{NodeId, NodeRef, Tree};
 
 #[derive(Debug, Clone)]
 struct FileP {
+    name: String,
     size: i64,
 }
 
@@ -28,79 +30,469 @@ enum Command {
     CdRoot,
     Ls(Vec<Subfolfile>)
 }
-fn parse_command(cmd: &str) -> Command {
+
+struct FileSystem {
+    tree: Tree<DirectoryData>,
+    curr_pos: NodeId,
+}
+
+struct CleanupPlanner {
+    disk_size: i64,
+    required_free: i64,
+}
+
+struct CleanupPlan {
+    deleted: Vec<(String,i64)>,
+    free_space: i64,
+}
+
+//smallest sum freeing enough space at a position of the cleanup knapsack, with the deleted directory and the sum before
+//deleting it, or None when it comes from the previous position
+type EnoughBucket = Option<(usize,Option<(usize,usize)>)>;
+
+//set of the sums from 0 to max, one bit each
+struct SumSet {
+    words: Vec<u64>,
+    max: usize,
+}
+
+fn parse_command(cmd: &str) -> Result<Command,String> {
     let mut it_lines = cmd.trim().split('\n');
     let cmd_line: Vec<&str> = it_lines.next().unwrap().split(' ').collect();
-    if cmd_line[0] == "cd" {
-        match cmd_line[1] {
-            ".." => Command::CdParent,
-            "/" => Command::CdRoot,
-            s => Command::CdSub(s.to_string())
+    match cmd_line[..] {
+        ["cd", ".."] => Ok(Command::CdParent),
+        ["cd", "/"] => Ok(Command::CdRoot),
+        ["cd", s] => Ok(Command::CdSub(s.to_string())),
+        ["ls"] => it_lines.map(|f_str| {
+            match f_str.split_once(' ') {
+                Some(("dir", name)) => Ok(Subfolfile::Folder(name.to_string())),
+                Some((size, name)) => i64::from_str(size)
+                    .map(|size| Subfolfile::File(FileP { name: name.to_string(), size }))
+                    .map_err(|_| format!("Invalid ls output {}", f_str)),
+                None => Err(format!("Invalid ls output {}", f_str))
+            }
+        }).collect::<Result<Vec<Subfolfile>,String>>().map(Command::Ls),
+        _ => Err(format!("Unknown command {}", cmd.trim()))
+    }
+}
+
+impl FileSystem {
+    fn new() -> Self {
+        let tree = Tree::new(DirectoryData { name: String::from("/"), sub_files: vec![] });
+        let curr_pos = tree.root().id();
+        FileSystem { tree, curr_pos }
+    }
+
+    fn from_transcript(transcript: &str) -> Result<Self,String> {
+        let mut file_system = FileSystem::new();
+        for cmd in transcript.split('$').filter(|s| !s.trim().is_empty()) {
+            file_system.apply_command(parse_command(cmd)?)?;
+        }
+        Ok(file_system)
+    }
+
+    fn child_directory(&self, pos: NodeId, name: &str) -> Option<NodeId> {
+        self.tree.get(pos).unwrap()
+            .children()
+            .find(|child| child.value().name == name)
+            .map(|child| child.id())
+    }
+
+    //running ls several times in the same directory does not duplicate its content
+    fn apply_command(&mut self, cmd: Command) -> Result<(),String> {
+        match cmd {
+            Command::CdSub(s) => {
+                self.curr_pos = self.child_directory(self.curr_pos, &s)
+                    .ok_or(format!("cd : no directory {} in {}", s, self.path(self.curr_pos)))?
+            }
+            Command::CdParent => {
+                self.curr_pos = self.tree.get(self.curr_pos).unwrap()
+                    .parent()
+                    .ok_or(String::from("cd : / has no parent directory"))?
+                    .id()
+            }
+            Command::CdRoot => {self.curr_pos = self.tree.root().id()}
+            Command::Ls(l) => {
+                for sub in l {
+                    match sub {
+                        Subfolfile::File(file_p) => {
+                            let mut node = self.tree.get_mut(self.curr_pos).unwrap();
+                            let sub_files = &mut node.value().sub_files;
+                            match sub_files.iter_mut().find(|file| file.name == file_p.name) {
+                                Some(file) => file.size = file_p.size,
+                                None => sub_files.push(file_p)
+                            }
+                        }
+                        Subfolfile::Folder(s) => {
+                            if self.child_directory(self.curr_pos, &s).is_none() {
+                                self.tree.get_mut(self.curr_pos).unwrap()
+                                    .append(DirectoryData { name: s, sub_files: vec![] });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, pos: NodeId) -> String {
+        let node = self.tree.get(pos).unwrap();
+        match node.parent() {
+            None => String::from("/"),
+            Some(parent) if parent.parent().is_none() => format!("/{}", node.value().name),
+            Some(parent) => format!("{}/{}", self.path(parent.id()), node.value().name)
+        }
+    }
+
+    fn find_directory(&self, path: &str) -> Option<NodeId> {
+        path.split('/')
+            .filter(|s| !s.is_empty())
+            .try_fold(self.tree.root().id(), |pos, name| self.child_directory(pos, name))
+    }
+
+    //sizes of every directory, children are handled before their parent
+    fn directory_sizes(&self) -> HashMap<NodeId,i64> {
+        let mut sizes: HashMap<NodeId,i64> = HashMap::new();
+        let nodes = self.tree.root().descendants().collect::<Vec<NodeRef<DirectoryData>>>();
+        for node in nodes.iter().rev() {
+            let size = node.value().sub_files.iter().map(|file| file.size).sum::<i64>()
+                + node.children().map(|child| sizes[&child.id()]).sum::<i64>();
+            sizes.insert(node.id(), size);
+        }
+        sizes
+    }
+
+    //total size of the directory or file at the given absolute path
+    fn size(&self, path: &str) -> Option<i64> {
+        if let Some(pos) = self.find_directory(path) {
+            return self.directory_sizes().get(&pos).copied();
         }
+        let (dir_path, name) = path.rsplit_once('/')?;
+        self.tree.get(self.find_directory(dir_path)?).unwrap()
+            .value()
+            .sub_files.iter()
+            .find(|file| file.name == name)
+            .map(|file| file.size)
+    }
+
+    //every directory with its total size, like du
+    fn du(&self) -> Vec<(String,i64)> {
+        let sizes = self.directory_sizes();
+        self.tree.root().descendants()
+            .map(|node| (self.path(node.id()), sizes[&node.id()]))
+            .collect()
+    }
+
+    //absolute paths of every directory or file with the given name
+    #[cfg(test)]
+    fn find_by_name(&self, name: &str) -> Vec<String> {
+        self.tree.root().descendants()
+            .flat_map(|node| {
+                let path = self.path(node.id());
+                let separator = if node.parent().is_none() { "" } else { "/" };
+                let dir = (node.value().name == name).then(|| path.clone());
+                let files = node.value().sub_files.iter()
+                    .filter(|file| file.name == name)
+                    .map(|file| format!("{}{}{}", path, separator, file.name))
+                    .collect::<Vec<String>>();
+                dir.into_iter().chain(files)
+            })
+            .collect()
+    }
+
+    fn fmt_directory(&self, f: &mut Formatter<'_>, node: NodeRef<DirectoryData>, depth: usize) -> std::fmt::Result {
+        writeln!(f, "{}- {} (dir)", "  ".repeat(depth), node.value().name)?;
+        for child in node.children() {
+            self.fmt_directory(f, child, depth + 1)?;
+        }
+        for file in node.value().sub_files.iter() {
+            writeln!(f, "{}- {} (file, size={})", "  ".repeat(depth + 1), file.name, file.size)?;
+        }
+        Ok(())
+    }
+}
+
+//prints the file system like the tree in the puzzle statement
+impl Display for FileSystem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_directory(f, self.tree.root(), 0)
+    }
+}
+
+impl CleanupPlanner {
+    fn needed_space(&self, file_system: &FileSystem) -> i64 {
+        self.required_free - (self.disk_size - file_system.size("/").unwrap())
+    }
 
+    //the smallest single directory which frees enough space
+    fn smallest_directory(&self, file_system: &FileSystem) -> Option<(String,i64)> {
+        let needed_space = self.needed_space(file_system);
+        file_system.du().into_iter()
+            .filter(|(_path,size)| *size >= needed_space)
+            .min_by_key(|(_path,size)| *size)
     }
-    else {
-        Command::Ls(it_lines.map(|f_str| {
-            let file_line: Vec<&str> = f_str.split(' ').collect();
-            if let Ok(size) = i64::from_str(file_line[0])  {
-                Subfolfile::File(FileP { size })
+
+    //the set of non-nested directories freeing enough space with the minimal total size, which is a knapsack over the
+    //directories in depth-first order : from the position of a directory, either it is deleted and the sums jump past
+    //its subtree, or it is kept and the sums go on with its first child. Only the sums below the needed space are kept,
+    //the others are gathered in a single bucket holding the smallest of them
+    fn plan(&self, file_system: &FileSystem) -> Option<CleanupPlan> {
+        let needed_space = self.needed_space(file_system);
+        let free_space = self.disk_size - file_system.size("/").unwrap();
+        if needed_space <= 0 {
+            return Some(CleanupPlan { deleted: vec![], free_space });
+        }
+        let needed_space = needed_space as usize;
+        let dir_sizes = file_system.directory_sizes();
+        let nodes = file_system.tree.root().descendants().collect::<Vec<NodeRef<DirectoryData>>>();
+        let position = nodes.iter()
+            .enumerate()
+            .map(|(i,node)| (node.id(),i))
+            .collect::<HashMap<NodeId,usize>>();
+        let sizes = nodes.iter().map(|node| dir_sizes[&node.id()] as usize).collect::<Vec<usize>>();
+        //position following the subtree of every directory
+        let mut subtree_end = vec![0; nodes.len()];
+        for (i,node) in nodes.iter().enumerate().rev() {
+            subtree_end[i] = node.last_child().map_or(i + 1, |child| subtree_end[position[&child.id()]]);
+        }
+        let mut enough: Vec<EnoughBucket> = vec![None; nodes.len() + 1];
+        //keeping a directory carries every sum to the next position, so the sums reachable at a position are those
+        //which became reachable at or before it, and only that first position is kept for each sum : 4 bytes per
+        //byte of needed space instead of a set per directory
+        let mut first_reached = vec![u32::MAX; needed_space];
+        let mut seen = SumSet::new(needed_space - 1);
+        sweep_sums(&subtree_end, &sizes, needed_space - 1, |i,sums| {
+            let (size,end) = (sizes[i],subtree_end[i]);
+            for sum in sums.difference(&seen) {
+                first_reached[sum] = i as u32;
             }
-            else {
-                Subfolfile::Folder(file_line[1].to_string())
+            seen.union_with(sums);
+            if let Some((sum,_origin)) = enough[i] {
+                if enough[i + 1].is_none_or(|(best,_origin)| sum < best) {
+                    enough[i + 1] = Some((sum,None));
+                }
             }
-        }).collect())
+            if let Some(previous) = sums.smallest_from(needed_space.saturating_sub(size)) {
+                if enough[end].is_none_or(|(best,_origin)| previous + size < best) {
+                    enough[end] = Some((previous + size,Some((i,previous))));
+                }
+            }
+        });
+        let reached_at = |sum: usize, i: usize| first_reached[sum] as usize <= i;
+        let (freed,_origin) = enough[nodes.len()]?;
+        let mut i = nodes.len();
+        let (last,mut sum) = loop {
+            match enough[i].unwrap() {
+                (_sum,Some(origin)) => break origin,
+                (_sum,None) => i -= 1
+            }
+        };
+        //goes back from the last deleted directory to any directory whose subtree ends before it and which brings the sum
+        let mut deleted = vec![last];
+        while sum > 0 {
+            let last = *deleted.last().unwrap();
+            let previous = (0..last)
+                .find(|&j| subtree_end[j] <= last && sizes[j] <= sum && reached_at(sum - sizes[j], j))
+                .unwrap();
+            deleted.push(previous);
+            sum -= sizes[previous];
+        }
+        let deleted = deleted.into_iter()
+            .rev()
+            .map(|i| (file_system.path(nodes[i].id()), sizes[i] as i64))
+            .collect();
+        Some(CleanupPlan { deleted, free_space: free_space + freed as i64 })
     }
 }
 
-fn apply_command(tree: &mut Tree<DirectoryData>, curr_pos: &mut NodeId, cmd: Command){
-    match cmd {
-        Command::CdSub(s) => {*curr_pos = tree.get(*curr_pos).unwrap().children().find(|child| child.value().name == s).unwrap().id()}
-        Command::CdParent => {*curr_pos = tree.get(*curr_pos).unwrap().parent().unwrap().id()}
-        Command::CdRoot => {*curr_pos = tree.root().id()}
-        Command::Ls(l) => {l.iter().for_each(|sub| {
-            match sub {
-                Subfolfile::File(file_p) => {tree.get_mut(*curr_pos).unwrap().value().sub_files.push(file_p.clone());}
-                Subfolfile::Folder(s) => {tree.get_mut(*curr_pos).unwrap().append(DirectoryData
-                { name: s.to_string(), sub_files: vec![] });}
+//goes through the knapsack positions, giving the sums below max reachable at each of them. Only the sets of the
+//positions still to come are kept : the next one and those following the subtrees of the current directory's
+//ancestors, so the memory grows with the depth of the tree rather than the number of directories
+fn sweep_sums(subtree_end: &[usize], sizes: &[usize], max: usize, mut visit: impl FnMut(usize, &SumSet)) {
+    let mut pending: HashMap<usize,SumSet> = HashMap::new();
+    let mut start = SumSet::new(max);
+    start.insert(0);
+    pending.insert(0, start);
+    for i in 0..sizes.len() {
+        let sums = pending.remove(&i).unwrap_or_else(|| SumSet::new(max));
+        visit(i, &sums);
+        pending.entry(subtree_end[i]).or_insert_with(|| SumSet::new(max)).union_shifted(&sums, sizes[i]);
+        match pending.get_mut(&(i + 1)) {
+            Some(next) => next.union_with(&sums),
+            None => {
+                pending.insert(i + 1, sums);
             }
-        })}
+        }
     }
 }
 
-fn size_node(tree: &Tree<DirectoryData>, pos: &NodeId, sizes: &mut HashMap<NodeId,i64>) -> i64 {
-    let node = tree.get(*pos).unwrap();
-    if let Some(u) = sizes.get(pos) {
-        *u
+impl SumSet {
+    fn new(max: usize) -> Self {
+        SumSet { words: vec![0; max / 64 + 1], max }
+    }
+
+    fn contains(&self, sum: usize) -> bool {
+        sum <= self.max && self.words[sum / 64] & (1 << (sum % 64)) != 0
+    }
+
+    fn smallest_from(&self, from: usize) -> Option<usize> {
+        (from..=self.max).find(|&sum| self.contains(sum))
+    }
+
+    //sums of this set missing from the other one
+    fn difference<'a>(&'a self, other: &'a SumSet) -> impl Iterator<Item = usize> + 'a {
+        self.words.iter()
+            .zip(other.words.iter())
+            .enumerate()
+            .flat_map(|(k,(&word,&other_word))| {
+                let mut bits = word & !other_word;
+                std::iter::from_fn(move || (bits != 0).then(|| {
+                    let bit = bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+                    k * 64 + bit
+                }))
+            })
+    }
+
+    fn insert(&mut self, sum: usize) {
+        if sum <= self.max {
+            self.words[sum / 64] |= 1 << (sum % 64);
+        }
+    }
+
+    fn union_with(&mut self, other: &SumSet) {
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= other_word;
+        }
+    }
+
+    //adds every sum of the other set increased by shift, those above max being dropped
+    fn union_shifted(&mut self, other: &SumSet, shift: usize) {
+        let (word_shift, bit_shift) = (shift / 64, shift % 64);
+        for k in (word_shift..self.words.len()).rev() {
+            let source = k - word_shift;
+            let mut shifted = other.words[source] << bit_shift;
+            if bit_shift > 0 && source > 0 {
+                shifted |= other.words[source - 1] >> (64 - bit_shift);
+            }
+            self.words[k] |= shifted;
+        }
+        if let Some(last) = self.words.last_mut() {
+            let nb_bits = self.max % 64 + 1;
+            if nb_bits < 64 {
+                *last &= (1 << nb_bits) - 1;
+            }
+        }
     }
-    else{
-        let mut result: i64 = node.value().sub_files.iter().map(|file| file.size).sum::<i64>();
-        node.children().for_each(|child| result += size_node(tree,& child.id(), sizes));
-        sizes.insert(*pos,result);
-        result
+}
+
+impl Display for CleanupPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (path, size) in self.deleted.iter() {
+            writeln!(f, "rm -r {} ({})", path, size)?;
+        }
+        write!(f, "Free space after cleanup : {}", self.free_space)
     }
 }
+
 pub fn day7() {
     let mut file = File::open("./inputs/input_day7.txt").expect("File not found");
     let mut data = String::new();
     file.read_to_string(&mut data)
         .expect("Error while reading file");
-    let mut directory_tree = Tree::new(DirectoryData { name: String::from("/"), sub_files: vec![] });
-    let mut curr_pos = directory_tree.root().id();
-    data.split('$')
-        .filter(|s| !s.is_empty())
-        .skip(1)
-        .map(parse_command)
-        .for_each(|v| apply_command(&mut directory_tree,&mut curr_pos,v));
-    let mut table: HashMap<NodeId,i64> = HashMap::new();
-    let sum = directory_tree.nodes()
-        .map(|node| size_node(&directory_tree,&node.id(),&mut table))
+    let file_system = FileSystem::from_transcript(&data).unwrap();
+    let sum = file_system.du().iter()
+        .map(|(_path,size)| *size)
         .filter(|&n| n <= 100_000)
         .sum::<i64>();
-    let needed_space = size_node(&directory_tree,&directory_tree.root().id(),&mut table) - 40_000_000;
-    let sum2 = table.values()
-        .filter(|&n| *n >= needed_space)
-        .min()
-        .unwrap();
+    let planner = CleanupPlanner { disk_size: 70_000_000, required_free: 30_000_000 };
+    let (_path,sum2) = planner.smallest_directory(&file_system).unwrap();
     println!("Solution 1 : {:?}",sum);
     println!("Solution 2 : {:?}",sum2);
-}
\ No newline at end of file
+    match planner.plan(&file_system) {
+        Some(plan) => println!("{}", plan),
+        None => println!("Not enough space can be freed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k";
+
+    #[test]
+    fn find_by_name_on_example() {
+        let file_system = FileSystem::from_transcript(EXAMPLE).unwrap();
+        assert_eq!(file_system.find_by_name("i"), vec![String::from("/a/e/i")]);
+        assert_eq!(file_system.find_by_name("e"), vec![String::from("/a/e")]);
+        assert_eq!(file_system.find_by_name("d.log"), vec![String::from("/d/d.log")]);
+        assert_eq!(file_system.find_by_name("b.txt"), vec![String::from("/b.txt")]);
+        assert!(file_system.find_by_name("z").is_empty());
+    }
+
+    #[test]
+    fn find_by_name_finds_directories_and_files() {
+        let file_system = FileSystem::from_transcript("$ cd /\n$ ls\ndir x\n10 y\n$ cd x\n$ ls\n20 x\n").unwrap();
+        assert_eq!(file_system.find_by_name("x"), vec![String::from("/x"), String::from("/x/x")]);
+    }
+
+    const PLANNER: CleanupPlanner = CleanupPlanner { disk_size: 70_000_000, required_free: 30_000_000 };
+
+    #[test]
+    fn plan_on_example() {
+        let file_system = FileSystem::from_transcript(EXAMPLE).unwrap();
+        let plan = PLANNER.plan(&file_system).unwrap();
+        assert_eq!(plan.deleted, vec![(String::from("/d"), 24933642)]);
+        assert_eq!(plan.free_space, 70_000_000 - 48381165 + 24933642);
+    }
+
+    #[test]
+    fn plan_mixing_levels() {
+        let transcript = "$ cd /\n$ ls\ndir a\ndir b\ndir c\n$ cd a\n$ ls\ndir x\ndir y\n100 f\n$ cd x\n$ ls\n300 f\n\
+            $ cd ..\n$ cd y\n$ ls\n500 f\n$ cd /\n$ cd b\n$ ls\n700 f\n$ cd ..\n$ cd c\n$ ls\n450 f\n";
+        let file_system = FileSystem::from_transcript(transcript).unwrap();
+        let planner = CleanupPlanner { disk_size: 3000, required_free: 1950 };
+        assert_eq!(planner.needed_space(&file_system), 1000);
+        let plan = planner.plan(&file_system).unwrap();
+        assert_eq!(plan.deleted, vec![(String::from("/a/x"), 300), (String::from("/b"), 700)]);
+        assert_eq!(plan.free_space, 1950);
+    }
+
+    #[test]
+    fn plan_on_real_sized_disk() {
+        //200 directories of about 200 kB under a root holding 42.3 MB, 2.3 MB have to be freed
+        let mut transcript = String::from("$ cd /\n$ ls\n2280100 big.bin\n");
+        transcript += &(0..200).map(|k| format!("dir d{}\n", k)).collect::<String>();
+        transcript += &(0..200).map(|k| format!("$ cd d{}\n$ ls\n{} f\n$ cd ..\n", k, 200_000 + k)).collect::<String>();
+        let file_system = FileSystem::from_transcript(&transcript).unwrap();
+        assert_eq!(PLANNER.needed_space(&file_system), 2_300_000);
+        let plan = PLANNER.plan(&file_system).unwrap();
+        //11 directories free at most 2 202 134, the 12 smallest ones are the best choice
+        assert_eq!(plan.deleted.len(), 12);
+        assert_eq!(plan.free_space, 30_000_000 - 2_300_000 + 2_400_066);
+    }
+}
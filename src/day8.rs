@@ -1,15 +1,25 @@
 use std::fs::File;
 use std::io::Read;
-use itertools::Itertools;
-use ndarray::{Array2, Axis, Dimension};
+use std::str::FromStr;
+use ndarray::{Array2, Dimension};
+
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+const DIRECTIONS: [Direction; 4] = [Direction::Left, Direction::Right, Direction::Top, Direction::Bottom];
 
 #[derive(Debug,Clone)]
 struct Tree {
     height: u32,
-    visible_from_left: bool,
-    visible_from_right: bool,
-    visible_from_top: bool,
-    visible_from_bottom: bool
+    //indexed by Direction, whether the tree can be seen from this edge of the forest
+    visible_from: [bool; 4],
+    //indexed by Direction, number of trees seen from this tree when looking this way
+    viewing_distance: [usize; 4],
 }
 
 impl Default for Tree {
@@ -19,18 +29,20 @@ impl Default for Tree {
 }
 impl Tree {
     fn is_visible_from_outside(&self) -> bool {
-        self.visible_from_bottom || self.visible_from_left || self.visible_from_top || self.visible_from_right
+        self.visible_from.iter().any(|&visible| visible)
     }
 
     fn make_tree(height : u32) -> Self {
         Tree {
             height,
-            visible_from_left: true,
-            visible_from_right: true,
-            visible_from_top: true,
-            visible_from_bottom: true,
+            visible_from: [true; 4],
+            viewing_distance: [0; 4],
         }
     }
+
+    fn scenic_score(&self) -> usize {
+        self.viewing_distance.iter().product()
+    }
 }
 #[derive(Debug)]
 struct Trees {
@@ -38,106 +50,228 @@ struct Trees {
 }
 
 impl Trees {
-    fn set_hiding(&mut self, i0: usize, j0: usize) {
-        let height_hiding_tree = self.trees[[i0,j0]].height;
+    //positions of a line of trees, starting from the edge the trees are looked at from
+    fn lines(&self, direction: Direction) -> Vec<Vec<(usize,usize)>> {
         let (height,width) = self.trees.raw_dim().into_pattern();
-        //above (vis from bot)
-        for k in 0..i0 {
-            if self.trees[[k,j0]].height <= height_hiding_tree {
-                self.trees[[k, j0]].visible_from_bottom = false;
-            }
+        match direction {
+            Direction::Left => (0..height).map(|i| (0..width).map(|j| (i,j)).collect()).collect(),
+            Direction::Right => (0..height).map(|i| (0..width).rev().map(|j| (i,j)).collect()).collect(),
+            Direction::Top => (0..width).map(|j| (0..height).map(|i| (i,j)).collect()).collect(),
+            Direction::Bottom => (0..width).map(|j| (0..height).rev().map(|i| (i,j)).collect()).collect(),
         }
-        //below (vis from top)
-        for k in i0+1..height {
-            if self.trees[[k,j0]].height <= height_hiding_tree {
-                self.trees[[k, j0]].visible_from_top = false;
-            }
-        }
-        //left (vis from right)
-        for k in 0..j0 {
-            if self.trees[[i0,k]].height <= height_hiding_tree {
-                self.trees[[i0,k]].visible_from_right = false;
-            }
-        }
-        //right (vis from left)
-        for k in j0+1..width {
-            if self.trees[[i0,k]].height <= height_hiding_tree {
-                self.trees[[i0,k]].visible_from_left = false;
+    }
+
+    //walks every line from the edge with a stack of the trees that are still not hidden by a taller one,
+    //which gives visibility from the edge and viewing distance towards it in O(rows·cols)
+    fn sweep(&mut self, direction: Direction) {
+        for line in self.lines(direction) {
+            let mut stack: Vec<usize> = Vec::new();
+            for (k,&pos) in line.iter().enumerate() {
+                let height = self.trees[pos].height;
+                while stack.last().is_some_and(|&top| self.trees[line[top]].height < height) {
+                    stack.pop();
+                }
+                let tree = &mut self.trees[pos];
+                match stack.last() {
+                    None => {
+                        tree.visible_from[direction as usize] = true;
+                        tree.viewing_distance[direction as usize] = k;
+                    }
+                    Some(&blocking) => {
+                        tree.visible_from[direction as usize] = false;
+                        tree.viewing_distance[direction as usize] = k - blocking;
+                    }
+                }
+                stack.push(k);
             }
         }
     }
 
-    fn compute_scenic_score(&self,i0: usize, j0: usize) -> usize {
-        let height_hiding_tree = self.trees[[i0,j0]].height;
+    #[cfg(test)]
+    fn step(&self, (i,j): (usize,usize), direction: Direction) -> Option<(usize,usize)> {
         let (height,width) = self.trees.raw_dim().into_pattern();
-        let mut result = 1;
-        //above
-        let mut c = (0..i0).rev()
-            .take_while(|&i| self.trees[[i,j0]].height < height_hiding_tree)
-            .count();
-        if c == i0 {
-            result *= c
-        }
-        else {
-            result *= c + 1
+        match direction {
+            Direction::Left => j.checked_sub(1).map(|j| (i,j)),
+            Direction::Right => (j + 1 < width).then_some((i,j + 1)),
+            Direction::Top => i.checked_sub(1).map(|i| (i,j)),
+            Direction::Bottom => (i + 1 < height).then_some((i + 1,j)),
         }
-        //below
-        c = (i0+1..height)
-            .take_while(|&i| self.trees[[i,j0]].height < height_hiding_tree)
-            .count();
-        if c == (height - (i0 + 1)) {
-            result *= c
-        }
-        else {
-            result *= c + 1
-        }
-        //left
-        c = (0..j0).rev()
-            .take_while(|&j| self.trees[[i0,j]].height < height_hiding_tree)
-            .count();
-        if c == j0 {
-            result *= c
-        }
-        else {
-            result *= c + 1
-        }
-        //right
-        c = (j0+1..width)
-            .take_while(|&j| self.trees[[i0,j]].height < height_hiding_tree)
-            .count();
-        if c == (width - (j0 + 1)) {
-            result *= c
+    }
+
+    //trees seen from the tree at (i,j) when looking in the four directions
+    #[cfg(test)]
+    fn visible_from_tree(&self, pos: (usize,usize)) -> Vec<(usize,usize)> {
+        let tree = &self.trees[pos];
+        DIRECTIONS.iter()
+            .flat_map(|&direction| {
+                std::iter::successors(self.step(pos, direction), move |&p| self.step(p, direction))
+                    .take(tree.viewing_distance[direction as usize])
+            })
+            .collect()
+    }
+
+    //trees seen from a point outside the forest, which must be in line with a row or a column
+    #[cfg(test)]
+    fn visible_from_point(&self, (x,y): (i64,i64)) -> Result<Vec<(usize,usize)>,String> {
+        let (height,width) = self.trees.raw_dim().into_pattern();
+        let (height,width) = (height as i64, width as i64);
+        let (first,direction) = if (0..height).contains(&x) && y < 0 {
+            ((x,0),Direction::Right)
+        } else if (0..height).contains(&x) && y >= width {
+            ((x,width - 1),Direction::Left)
+        } else if (0..width).contains(&y) && x < 0 {
+            ((0,y),Direction::Bottom)
+        } else if (0..width).contains(&y) && x >= height {
+            ((height - 1,y),Direction::Top)
+        } else {
+            return Err(format!("({},{}) is not outside the forest in line with a row or a column", x, y));
+        };
+        let first = (first.0 as usize, first.1 as usize);
+        let mut tallest = None;
+        Ok(std::iter::successors(Some(first), |&p| self.step(p, direction))
+            .filter(|&p| {
+                let height = self.trees[p].height;
+                let visible = tallest.is_none_or(|tallest| height > tallest);
+                tallest = tallest.max(Some(height));
+                visible
+            })
+            .collect())
+    }
+
+    //trees of the row which are visible from one of its ends
+    #[cfg(test)]
+    fn visible_in_row(&self, i: usize) -> Vec<(usize,usize)> {
+        let (_height,width) = self.trees.raw_dim().into_pattern();
+        (0..width)
+            .map(|j| (i,j))
+            .filter(|&pos| self.trees[pos].visible_from[Direction::Left as usize]
+                || self.trees[pos].visible_from[Direction::Right as usize])
+            .collect()
+    }
+
+    //trees of the column which are visible from one of its ends
+    #[cfg(test)]
+    fn visible_in_column(&self, j: usize) -> Vec<(usize,usize)> {
+        let (height,_width) = self.trees.raw_dim().into_pattern();
+        (0..height)
+            .map(|i| (i,j))
+            .filter(|&pos| self.trees[pos].visible_from[Direction::Top as usize]
+                || self.trees[pos].visible_from[Direction::Bottom as usize])
+            .collect()
+    }
+
+    fn scenic_heatmap(&self) -> Array2<usize> {
+        self.trees.map(|tree| tree.scenic_score())
+    }
+
+    fn compute_visibility(&mut self) {
+        for direction in DIRECTIONS {
+            self.sweep(direction);
         }
-        else {
-            result *= c + 1
+    }
+}
+
+impl FromStr for Trees {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows = s.lines()
+            .map(|line| line.chars()
+                .map(|c| c.to_digit(10).map(Tree::make_tree).ok_or(format!("Invalid height {}", c)))
+                .collect::<Result<Vec<Tree>,String>>())
+            .collect::<Result<Vec<Vec<Tree>>,String>>()?;
+        let width = rows.first().map_or(0, |row| row.len());
+        if rows.iter().any(|row| row.len() != width) {
+            return Err(String::from("Every row of the forest must have the same length"));
         }
-        result
+        let height = rows.len();
+        let trees = Array2::from_shape_vec((height,width), rows.concat())
+            .map_err(|e| e.to_string())?;
+        let mut trees = Trees { trees };
+        trees.compute_visibility();
+        Ok(trees)
     }
 }
+
 pub fn day8() {
     let mut file = File::open("./inputs/input_day8.txt").expect("File not found");
     let mut data = String::new();
     file.read_to_string(&mut data)
         .expect("Error while reading file");
 
-    let length_size = data.split('\n').count();
-    let mut array: Array2<Tree> = Array2::default((length_size,length_size));
-    data.split('\n')
-        .zip(array.axis_iter_mut(Axis(0)))
-        .for_each(|(u,mut row)| u.chars().zip(row.iter_mut())
-            .for_each(|(c,tree)| *tree = Tree::make_tree(c.to_digit(10).unwrap())));
-    let mut trees = Trees { trees: array };
-    (0..length_size)
-        .for_each(|i| (0..length_size)
-            .for_each(|j| trees.set_hiding(i,j)));
+    let trees = Trees::from_str(&data).unwrap();
     let nb_visible = trees.trees.iter()
         .filter(|t| t.is_visible_from_outside())
         .count();
     println!("Solution 1 : {:?}",nb_visible);
-    let v = (0..length_size)
-        .cartesian_product(0..length_size)
-        .map(|(i,j)| trees.compute_scenic_score(i,j))
-        .max();
-    println!("Solution 2 : {:?}",v.unwrap());
+    let heatmap = trees.scenic_heatmap();
+    println!("Solution 2 : {:?}",heatmap.iter().max().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-}
\ No newline at end of file
+    const EXAMPLE: &str = "30373
+25512
+65332
+33549
+35390";
+
+    #[test]
+    fn visibility_on_example() {
+        let trees = Trees::from_str(EXAMPLE).unwrap();
+        assert_eq!(trees.trees.iter().filter(|t| t.is_visible_from_outside()).count(), 21);
+        assert_eq!(trees.scenic_heatmap().iter().max(), Some(&8));
+    }
+
+    #[test]
+    fn visible_in_row_and_column() {
+        let trees = Trees::from_str(EXAMPLE).unwrap();
+        assert_eq!(trees.visible_in_row(0), vec![(0,0),(0,3),(0,4)]);
+        assert_eq!(trees.visible_in_row(2), vec![(2,0),(2,1),(2,3),(2,4)]);
+        assert_eq!(trees.visible_in_column(0), vec![(0,0),(2,0),(4,0)]);
+        assert_eq!(trees.visible_in_column(3), vec![(0,3),(4,3)]);
+    }
+
+    #[test]
+    fn visible_from_points() {
+        let trees = Trees::from_str(EXAMPLE).unwrap();
+        assert_eq!(trees.visible_from_point((0,-1)), Ok(vec![(0,0),(0,3)]));
+        assert_eq!(trees.visible_from_point((5,2)), Ok(vec![(4,2),(3,2)]));
+        assert!(trees.visible_from_point((-1,-1)).is_err());
+        assert_eq!(trees.visible_from_tree((3,2)).len(), 2 + 2 + 1 + 2);
+    }
+
+    //3 rows of 7 trees, so that rows and columns cannot be mixed up
+    const WIDE: &str = "1234321
+2511052
+3339333";
+
+    #[test]
+    fn visibility_on_wide_forest() {
+        let trees = Trees::from_str(WIDE).unwrap();
+        assert_eq!(trees.trees.dim(), (3,7));
+        let hidden = trees.trees.indexed_iter()
+            .filter(|(_pos,t)| !t.is_visible_from_outside())
+            .map(|(pos,_t)| pos)
+            .collect::<Vec<(usize,usize)>>();
+        assert_eq!(hidden, vec![(1,2),(1,3),(1,4)]);
+        let heatmap = trees.scenic_heatmap();
+        assert_eq!(heatmap.row(0).to_vec(), vec![0; 7]);
+        assert_eq!(heatmap.row(1).to_vec(), vec![0, 4, 1, 2, 1, 4, 0]);
+        assert_eq!(heatmap.row(2).to_vec(), vec![0; 7]);
+    }
+
+    #[test]
+    fn queries_on_wide_forest() {
+        let trees = Trees::from_str(WIDE).unwrap();
+        assert_eq!(trees.visible_in_row(1), vec![(1,0),(1,1),(1,5),(1,6)]);
+        assert_eq!(trees.visible_in_column(3), vec![(0,3),(2,3)]);
+        assert_eq!(trees.visible_from_point((1,7)), Ok(vec![(1,6),(1,5)]));
+        assert_eq!(trees.visible_from_point((3,3)), Ok(vec![(2,3)]));
+        assert_eq!(trees.visible_from_point((-1,6)), Ok(vec![(0,6),(1,6),(2,6)]));
+        assert!(trees.visible_from_point((-1,7)).is_err());
+        assert_eq!(trees.visible_from_tree((1,1)), vec![(1,0),(1,2),(1,3),(1,4),(1,5),(0,1),(2,1)]);
+    }
+}
@@ -1,7 +1,9 @@
 use std::cmp::max;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Read;
+use std::str::FromStr;
+use itertools::Itertools;
 
 #[derive(Debug, Default)]
 struct Head {
@@ -15,101 +17,176 @@ struct Tail {
     pos_y: i32,
 }
 
+#[derive(Debug)]
+struct Instruction {
+    displacement: (i32,i32),
+    nb_steps: usize,
+}
+
 fn d_inf(p1: (i32,i32), p2: (i32,i32)) -> i32 {
     max((p1.0 - p2.0).abs(),(p1.1 - p2.1).abs())
 }
 impl Tail {
-    fn closest_between(&self, p1: (i32,i32), p2: (i32,i32)) -> (i32,i32) {
-        let d1 = d_inf((self.pos_x,self.pos_y),p1);
-        let d2 = d_inf((self.pos_x,self.pos_y),p2);
-        if d1 < d2 {
-            p1
-        }
-        else {
-            p2
-        }
-    }
-    fn move_from_followed_move(&mut self, movem: (i32,i32), followed_pos: (i32,i32)) {
-        let (dx,dy) = movem;
-        let (xn,yn) = followed_pos;
-        let (x0,y0) = (xn - dx,yn - dy);
-        if dx == 0 || dy == 0 {
-            if (xn - self.pos_x).abs() == 2 || (yn - self.pos_y).abs() == 2{
-                (self.pos_x, self.pos_y) = (x0,y0)
-            }
-        }
-        else{
-            let anti_diag = (x0 - dx, y0 - dy);
-            if d_inf((self.pos_x,self.pos_y),followed_pos) <= 1 {
-
-            }
-            else if (self.pos_x, self.pos_y) == anti_diag {
-                (self.pos_x, self.pos_y) = (x0,y0)
-            }
-            else {
-                (self.pos_x, self.pos_y) = self.closest_between((x0, y0 + dy),(x0 + dx, y0));
-            }
+    //steps once towards the followed knot on each axis as soon as they are not touching anymore
+    fn move_from_followed_move(&mut self, followed_pos: (i32,i32)) {
+        if d_inf((self.pos_x,self.pos_y),followed_pos) > 1 {
+            self.pos_x += (followed_pos.0 - self.pos_x).signum();
+            self.pos_y += (followed_pos.1 - self.pos_y).signum();
         }
     }
 }
-struct SystemMultipleTails {
+
+struct Rope {
     head: Head,
     tails: Vec<Tail>,
-    visited_last_tail_pos: HashSet<(i32,i32)>
+    //indexed by knot, the head being the knot 0
+    visited: Vec<HashSet<(i32,i32)>>,
+    trajectories: HashMap<usize,Vec<(i32,i32)>>,
 }
 
-impl SystemMultipleTails{
+impl Rope {
     fn new(nb_tails: usize) -> Self {
-        SystemMultipleTails {
+        Rope {
             head: Default::default(),
             tails: vec![Default::default();nb_tails],
-            visited_last_tail_pos: HashSet::new(),
+            visited: vec![HashSet::from([(0,0)]);nb_tails + 1],
+            trajectories: HashMap::new(),
+        }
+    }
+
+    fn knot(&self, knot: usize) -> (i32,i32) {
+        match knot {
+            0 => (self.head.pos_x,self.head.pos_y),
+            k => (self.tails[k-1].pos_x,self.tails[k-1].pos_y)
         }
     }
 
+    //starts recording every position of the knot
+    fn track(&mut self, knot: usize) {
+        self.trajectories.insert(knot, vec![self.knot(knot)]);
+    }
+
+    //moves the head by one step, which can be diagonal
     fn move_head(&mut self, move_head: (i32,i32)) {
         self.head.pos_x += move_head.0;
         self.head.pos_y += move_head.1;
         let mut curr_pos = (self.head.pos_x, self.head.pos_y);
-        let mut movet = move_head;
         for t in self.tails.iter_mut(){
-            let (xb,yb) = (t.pos_x,t.pos_y);
-            t.move_from_followed_move(movet,curr_pos);
-            movet = (t.pos_x - xb, t.pos_y - yb);
+            t.move_from_followed_move(curr_pos);
             curr_pos = (t.pos_x,t.pos_y);
         }
-        let last_tail = self.tails.last().unwrap();
-        self.visited_last_tail_pos.insert((last_tail.pos_x,last_tail.pos_y));
+        for knot in 0..=self.tails.len() {
+            let pos = self.knot(knot);
+            self.visited[knot].insert(pos);
+            if let Some(trajectory) = self.trajectories.get_mut(&knot) {
+                trajectory.push(pos);
+            }
+        }
+    }
+
+    fn apply(&mut self, instruction: &Instruction) {
+        for _ in 0..instruction.nb_steps {
+            self.move_head(instruction.displacement);
+        }
+    }
+
+    fn visited(&self, knot: usize) -> &HashSet<(i32,i32)> {
+        &self.visited[knot]
+    }
+
+    fn trajectory(&self, knot: usize) -> Option<&Vec<(i32,i32)>> {
+        self.trajectories.get(&knot)
+    }
+}
+
+//directions can be combined for diagonal moves, as in "UR 4"
+impl FromStr for Instruction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (directions, n) = s.split_once(' ').ok_or(format!("Invalid instruction {}", s))?;
+        let displacement = directions.chars()
+            .map(|c| match c {
+                'R' => Ok((1,0)),
+                'U' => Ok((0,1)),
+                'L' => Ok((-1,0)),
+                'D' => Ok((0,-1)),
+                _ => Err(format!("Invalid direction {}", c))
+            })
+            .fold_ok((0,0), |accum: (i32,i32), item: (i32,i32)| (accum.0 + item.0, accum.1 + item.1))?;
+        if d_inf(displacement,(0,0)) != 1 {
+            return Err(format!("Invalid direction {}", directions));
+        }
+        let nb_steps = n.parse::<usize>().map_err(|e| e.to_string())?;
+        Ok(Instruction { displacement, nb_steps })
     }
 }
+
 pub fn day9() {
     let mut file = File::open("./inputs/input_day9.txt").expect("File not found");
     let mut data = String::new();
     file.read_to_string(&mut data)
         .expect("Error while reading file");
 
-    let inst: Vec<(i32,i32)> = data.split('\n')
-        .map(|s| {
-            let mut it = s.split_whitespace();
-            let displacement = match it.next().unwrap() {
-                "R" => (1,0),
-                "U" => (0,1),
-                "L" => (-1,0),
-                "D" => (0,-1),
-                _ => panic!()
-            };
-            let n: usize = it.next().unwrap().parse().unwrap();
-            vec![displacement; n]
-        })
-        .reduce(|accum, item| [accum,item].concat())
-        .unwrap();
-    let mut system1 = SystemMultipleTails::new(1);
-    let mut system10 = SystemMultipleTails::new(9);
-    inst.iter()
-        .for_each(|movet| {
-            system1.move_head(*movet);
-            system10.move_head(*movet);
-        });
-    println!("Solution 1 : {:?}", system1.visited_last_tail_pos.len());
-    println!("Solution 2 : {:?}", system10.visited_last_tail_pos.len());
-}
\ No newline at end of file
+    let mut rope = Rope::new(9);
+    rope.track(9);
+    data.lines()
+        .map(|s| Instruction::from_str(s).unwrap())
+        .for_each(|instruction| rope.apply(&instruction));
+    println!("Solution 1 : {:?}", rope.visited(1).len());
+    println!("Solution 2 : {:?}", rope.visited(9).len());
+    println!("Last knot moves : {:?}", rope.trajectory(9).unwrap().iter().dedup().count() - 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //previous rule, where the tail position is deduced from the move of the followed knot
+    fn legacy_move_from_followed_move(tail: (i32,i32), movem: (i32,i32), followed_pos: (i32,i32)) -> (i32,i32) {
+        let (dx,dy) = movem;
+        let (xn,yn) = followed_pos;
+        let (x0,y0) = (xn - dx,yn - dy);
+        if dx == 0 || dy == 0 {
+            if (xn - tail.0).abs() == 2 || (yn - tail.1).abs() == 2 {
+                (x0,y0)
+            }
+            else {
+                tail
+            }
+        }
+        else if d_inf(tail,followed_pos) <= 1 {
+            tail
+        }
+        else if tail == (x0 - dx, y0 - dy) {
+            (x0,y0)
+        }
+        else if d_inf(tail,(x0, y0 + dy)) < d_inf(tail,(x0 + dx, y0)) {
+            (x0, y0 + dy)
+        }
+        else {
+            (x0 + dx, y0)
+        }
+    }
+
+    //checks that the sign-step rule agrees with the previous rule for every tail touching the followed knot and every move
+    #[test]
+    fn sign_step_rule_matches_legacy_rule() {
+        let offsets = (-1..=1).cartesian_product(-1..=1).collect::<Vec<(i32,i32)>>();
+        for (&tail_pos,&movem) in offsets.iter().cartesian_product(offsets.iter().filter(|&&movem| movem != (0,0))) {
+            let mut tail = Tail { pos_x: tail_pos.0, pos_y: tail_pos.1 };
+            tail.move_from_followed_move(movem);
+            assert_eq!((tail.pos_x,tail.pos_y), legacy_move_from_followed_move(tail_pos, movem, movem),
+                       "tail at {:?}, followed knot moving by {:?}", tail_pos, movem);
+        }
+    }
+
+    #[test]
+    fn example_rope() {
+        let mut rope = Rope::new(9);
+        "R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2".lines()
+            .for_each(|s| rope.apply(&Instruction::from_str(s).unwrap()));
+        assert_eq!(rope.visited(1).len(), 13);
+        assert_eq!(rope.visited(9).len(), 1);
+    }
+}
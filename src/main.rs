@@ -1,11 +1,11 @@
 use std::io;
 use std::time::Instant;
 use crate::day10::day10;
-use crate::day11::day11;
+use crate::day11::{day11, RoundSolver};
 use crate::day12::day12;
 use crate::day13::day13;
 use crate::day14::day14;
-use crate::day15::day15;
+use crate::day15::{day15, SearchParameters};
 use crate::day16::day16;
 use crate::day17::day17;
 use crate::day18::day18;
@@ -53,7 +53,7 @@ mod day23;
 mod day24;
 mod day25;
 
-fn time_function(f : fn() -> ()) {
+fn time_function(f : impl FnOnce()) {
     let now = Instant::now();
     f();
     let elapsed_time = now.elapsed();
@@ -81,11 +81,27 @@ fn main() {
         8 => {time_function(day8)}
         9 => {time_function(day9)}
         10 => {time_function(day10)}
-        11 => {time_function(day11)}
+        11 => {
+            println!("Choose the solver for the rounds (simulation or cycles) !");
+            let mut solver_choice = String::new();
+            io::stdin()
+                .read_line(&mut solver_choice)
+                .expect("Failed to read line");
+            let solver = solver_choice.parse::<RoundSolver>().expect("Invalid solver ! ");
+            time_function(|| day11(solver))
+        }
         12 => {time_function(day12)}
         13 => {time_function(day13)}
         14 => {time_function(day14)}
-        15 => {time_function(day15)}
+        15 => {
+            println!("Choose the search parameters (example, real or row,max) !");
+            let mut parameters_choice = String::new();
+            io::stdin()
+                .read_line(&mut parameters_choice)
+                .expect("Failed to read line");
+            let parameters = parameters_choice.parse::<SearchParameters>().expect("Invalid search parameters ! ");
+            time_function(|| day15(parameters))
+        }
         16 => {time_function(day16)}
         17 => {time_function(day17)}
         18 => {time_function(day18)}